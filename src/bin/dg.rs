@@ -4,8 +4,10 @@ use dialogical::Args;
 fn main() {
     let args = Args::parse();
 
-    if let Err(e) = dialogical::cli_main(args, None) {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
-    }
+    let Err(e) = dialogical::cli_main(args, None) else {
+        return;
+    };
+
+    dialogical::print_error(&e);
+    std::process::exit(1);
 }
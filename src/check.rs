@@ -0,0 +1,107 @@
+//!
+//! Static checks that run on a fully parsed `InteractionMap`,
+//! after every `Import` has been pulled in
+//!
+
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::{InteractionMap, Label};
+
+/// A goto pointing at an interaction ID that doesn't exist
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DanglingGoto {
+    /// ID of the interaction the goto is in
+    pub interaction: String,
+
+    /// Index of the page the interaction's ending is on
+    pub page: usize,
+
+    /// Index of the choice the goto belongs to, or `None`
+    /// if the goto is the interaction's whole ending
+    pub choice: Option<usize>,
+
+    /// The ID that doesn't exist
+    pub target: String,
+}
+
+impl fmt::Display for DanglingGoto {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (page {}", self.interaction, self.page + 1)?;
+
+        if let Some(choice) = self.choice {
+            write!(f, ", choice {}", choice + 1)?;
+        }
+
+        write!(f, "): goto {} is not an interaction", self.target)
+    }
+}
+
+/// Every dangling goto found by `check_gotos`
+#[derive(Debug, Error, PartialEq)]
+#[error("Found {} goto(s) to interactions that don't exist", .0.len())]
+pub struct DanglingGotos(pub Vec<DanglingGoto>);
+
+/// Find every goto, in both interaction endings and choices,
+/// that doesn't name an interaction in `map`.
+///
+/// Sorted by interaction ID, so the output is stable.
+pub fn check_gotos(map: &InteractionMap) -> Vec<DanglingGoto> {
+    let mut res = vec![];
+
+    for (id, ix) in map {
+        for (choice, label) in ix.ending.labels() {
+            let Label::Goto(target) = label;
+
+            if !map.contains_key(target) {
+                res.push(DanglingGoto {
+                    interaction: id.clone(),
+                    page: ix.pages.len().saturating_sub(1),
+                    choice,
+                    target: target.clone(),
+                });
+            }
+        }
+    }
+
+    res.sort();
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::parse_dummy;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn no_dangling() {
+        assert!(check_gotos(&parse_dummy("rodrick.dg")).is_empty());
+        assert!(check_gotos(&parse_dummy("pets/main.dg")).is_empty());
+    }
+
+    #[test]
+    fn dangling_ending_and_choice() {
+        let res = check_gotos(&parse_dummy("dangling.dg"));
+
+        assert_eq!(
+            res,
+            vec![
+                DanglingGoto {
+                    interaction: "Shop".into(),
+                    page: 1,
+                    choice: Some(1),
+                    target: "Shop_Sell".into(),
+                },
+                DanglingGoto {
+                    interaction: "Shop_Buy".into(),
+                    page: 0,
+                    choice: None,
+                    target: "Shop_Exit".into(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,719 @@
+//!
+//! The `dg` command line interface. Each subcommand is a thin
+//! wrapper that also handles stdin/stdout around one of the
+//! library functions, so build scripts can skip the CLI and
+//! call those directly.
+//!
+
+use clap::{Parser, Subcommand};
+
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::play::pick_entry;
+use crate::{
+    check_using, compile_using, decompile, deserialize_as, dump, dump_json, format_file,
+    format_source, graph_using, pack, parse_file_using, parse_transcripts, play, render_diagnostic,
+    run_transcript, serialize, write_depfile, DanglingGotos, DepfileFormat, DgParser, Error,
+    FsSource, GraphFormat, Interaction, InteractionMap, OutputFormat, ParseErrors, SourceProvider,
+    Unformatted,
+};
+
+pub(crate) static SILENT: AtomicBool = AtomicBool::new(false);
+
+macro_rules! log {
+    ($($arg:tt)*) => {
+        if !SILENT.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+#[derive(Parser, Debug)]
+#[command(arg_required_else_help(true))]
+#[command(args_conflicts_with_subcommands(true))]
+#[command(author, version, about)]
+/// P/E/T/S Dialogue Compiler
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Running `dg` without a subcommand is the same as `dg compile`
+    #[command(flatten)]
+    pub compile: CompileArgs,
+
+    /// Silences progress "info" stderr messages.
+    #[arg(short, long, global = true)]
+    pub silent: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Compile a `.dg` file into a packed `.dgc`
+    Compile(CompileArgs),
+
+    /// Check a file for errors and gotos that lead nowhere,
+    /// without writing any output
+    Check(CheckArgs),
+
+    /// Draw a graph of which interactions lead to which
+    Graph(GraphArgs),
+
+    /// Pretty-print a compiled file
+    Dump(DumpArgs),
+
+    /// Turn a compiled file back into `.dg` source
+    Decompile(DecompileArgs),
+
+    /// Rewrite `.dg` files in the canonical layout
+    Fmt(FmtArgs),
+
+    /// Playtest dialogue in the terminal
+    Play(PlayArgs),
+
+    /// Check that dialogue plays out like the transcripts
+    /// in some files say it should
+    Test(TestArgs),
+
+    /// Compile a file, then compile it again every time it
+    /// or anything it imports changes
+    Watch(WatchArgs),
+}
+
+/// Where to look for files that get imported
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct ImportArgs {
+    /// Also look for imported files in this folder, if they're
+    /// not next to the file importing them. Can be given more
+    /// than once, and they're searched in order.
+    #[arg(short = 'I', long = "include-dir")]
+    pub include_dirs: Vec<PathBuf>,
+
+    /// Folder that `Import @/...` paths are relative to.
+    /// Defaults to the one the input file is in.
+    #[arg(long)]
+    pub root: Option<PathBuf>,
+}
+
+impl ImportArgs {
+    /// A parser for `path` that looks for imports in the right places
+    fn parser(&self, path: PathBuf, cwd: Option<&Path>) -> DgParser {
+        let join = |dir: &PathBuf| match cwd {
+            Some(cwd) => cwd.join(dir),
+            None => dir.clone(),
+        };
+
+        let parser = DgParser::new(path).include_dirs(self.include_dirs.iter().map(join));
+        match self.root {
+            Some(ref root) => parser.project_root(join(root)),
+            None => parser,
+        }
+    }
+}
+
+/// What the compiled output should look like
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct OutputArgs {
+    #[arg(short, long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+
+    /// Include a table of contents in the `.dgc`, so games can
+    /// load interactions one at a time. Only for bincode.
+    #[arg(long)]
+    pub toc: bool,
+
+    /// Write the speaker and vox in effect on every page, instead
+    /// of only the changes. See `Interaction::resolve_metadata`.
+    #[arg(long)]
+    pub resolve_metadata: bool,
+}
+
+impl OutputArgs {
+    /// Turn parsed interactions into the bytes that get written out
+    pub(crate) fn compile(&self, mut map: InteractionMap) -> Result<Vec<u8>, Error> {
+        if self.resolve_metadata {
+            map.values_mut().for_each(Interaction::resolve_metadata);
+        }
+
+        match self.format {
+            OutputFormat::Bincode => pack(&map, self.toc),
+            format => serialize(&map, format),
+        }
+    }
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct CompileArgs {
+    /// The input file, or stdin if not specified
+    pub file: Option<String>,
+
+    /// The output file, or stdout if not specified
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    #[command(flatten)]
+    pub out: OutputArgs,
+
+    /// Also write a list of every file that was read, next to
+    /// the output file, for build systems to pick up
+    #[arg(long, value_enum, requires = "output")]
+    pub deps: Option<DepfileFormat>,
+
+    #[command(flatten)]
+    pub imports: ImportArgs,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct CheckArgs {
+    /// The input file, or stdin if not specified
+    pub file: Option<String>,
+
+    /// Interaction(s) the game can start dialogue from.
+    /// If given, also warns about unreachable dialogue.
+    #[arg(short, long)]
+    pub entry: Vec<String>,
+
+    #[command(flatten)]
+    pub imports: ImportArgs,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct GraphArgs {
+    /// The input file, or stdin if not specified
+    pub file: Option<String>,
+
+    /// The output file, or stdout if not specified
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    #[arg(short, long, value_enum, default_value_t)]
+    pub format: GraphFormat,
+
+    #[command(flatten)]
+    pub imports: ImportArgs,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct DumpArgs {
+    /// The compiled input file, or stdin if not specified.
+    /// Read as whichever format its extension says, or
+    /// as a `.dgc` if that's not clear.
+    pub file: Option<String>,
+
+    /// The output file, or stdout if not specified
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// Dump as JSON instead of plain text
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct DecompileArgs {
+    /// The compiled input file, or stdin if not specified.
+    /// Read as whichever format its extension says, or
+    /// as a `.dgc` if that's not clear.
+    pub file: Option<String>,
+
+    /// The output file, or stdout if not specified
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct FmtArgs {
+    /// The files to format in place. If none are given,
+    /// formats stdin and prints the result to stdout.
+    pub files: Vec<String>,
+
+    /// Don't write anything, just fail if any
+    /// of the files aren't formatted
+    #[arg(long)]
+    pub check: bool,
+
+    #[command(flatten)]
+    pub imports: ImportArgs,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct PlayArgs {
+    /// The `.dg` or compiled file to play
+    pub file: String,
+
+    /// Interaction to start from. If not given,
+    /// asks which one to start from.
+    #[arg(short, long)]
+    pub entry: Option<String>,
+
+    #[command(flatten)]
+    pub imports: ImportArgs,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct TestArgs {
+    /// The `.dg` or compiled file to test
+    pub file: String,
+
+    /// Transcript files with the tests to run
+    #[arg(required = true)]
+    pub transcripts: Vec<String>,
+
+    #[command(flatten)]
+    pub imports: ImportArgs,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct WatchArgs {
+    /// The input file
+    pub file: String,
+
+    /// The output file
+    #[arg(short, long)]
+    pub output: String,
+
+    #[command(flatten)]
+    pub out: OutputArgs,
+
+    /// How often to check for changes, in milliseconds
+    #[arg(long, default_value_t = 500)]
+    pub interval: u64,
+
+    #[command(flatten)]
+    pub imports: ImportArgs,
+}
+
+pub fn cli_main(args: Args, cwd: Option<&Path>) -> Result<(), Error> {
+    SILENT.store(args.silent, Ordering::Relaxed);
+
+    match args.command {
+        Some(Command::Compile(args)) => compile_main(args, cwd),
+        Some(Command::Check(args)) => check_main(args, cwd),
+        Some(Command::Graph(args)) => graph_main(args, cwd),
+        Some(Command::Dump(args)) => dump_main(args),
+        Some(Command::Decompile(args)) => decompile_main(args),
+        Some(Command::Fmt(args)) => fmt_main(args, cwd),
+        Some(Command::Play(args)) => play_main(args, cwd),
+        Some(Command::Test(args)) => test_main(args, cwd),
+        Some(Command::Watch(args)) => watch_main(args, cwd),
+
+        None => compile_main(args.compile, cwd),
+    }
+}
+
+fn compile_main(args: CompileArgs, cwd: Option<&Path>) -> Result<(), Error> {
+    let stdin = args.file.is_none();
+    let parser = input_parser(args.file, &args.imports, cwd)?;
+
+    log!("Compiling...");
+    let (res, mut inputs) = compile_using(parser, &args.out)?;
+
+    // stdin isn't a file a build system could watch
+    if stdin {
+        inputs.remove(0);
+    }
+
+    log!("Writing...");
+    write_output(args.output.clone(), &res)?;
+
+    // clap makes sure there's an output file if this is set
+    if let (Some(format), Some(output)) = (args.deps, args.output) {
+        let path = write_depfile(output.as_ref(), &inputs, format)?;
+        log!("Wrote dependencies to {}", path.display());
+    }
+
+    log!("Done!");
+    Ok(())
+}
+
+/// Parse the file, then make sure every goto
+/// leads to an interaction that actually exists
+///
+/// If any entry points are given, also warn about
+/// dialogue that can't be reached or can't be left.
+fn check_main(args: CheckArgs, cwd: Option<&Path>) -> Result<(), Error> {
+    let parser = input_parser(args.file, &args.imports, cwd)?;
+
+    log!("Checking...");
+    let reach = check_using(parser, &args.entry)?;
+
+    if !args.entry.is_empty() {
+        for id in &reach.unknown_entries {
+            eprintln!("warning: entry {} is not an interaction", id);
+        }
+
+        for id in &reach.unreachable {
+            eprintln!("warning: {} can never be reached", id);
+        }
+
+        for cycle in &reach.endless_cycles {
+            eprintln!(
+                "warning: {} loop forever with no way out",
+                cycle.join(" -> ")
+            );
+        }
+
+        for id in &reach.looping_choices {
+            eprintln!("warning: every choice in {} leads back to it", id);
+        }
+    }
+
+    log!("No errors found!");
+    Ok(())
+}
+
+/// Parse the file, then draw a graph of which
+/// interactions lead to which
+fn graph_main(args: GraphArgs, cwd: Option<&Path>) -> Result<(), Error> {
+    let parser = input_parser(args.file, &args.imports, cwd)?;
+
+    log!("Drawing graph...");
+    write_output(args.output, graph_using(parser, args.format)?.as_bytes())?;
+
+    log!("Done!");
+    Ok(())
+}
+
+/// Deserialize a compiled file and print what's inside
+fn dump_main(args: DumpArgs) -> Result<(), Error> {
+    let res = read_compiled(args.file)?;
+
+    let res = if args.json {
+        dump_json(&res)?
+    } else {
+        dump(&res)
+    };
+
+    write_output(args.output, res.as_bytes())
+}
+
+/// Deserialize a compiled file and print it as source
+fn decompile_main(args: DecompileArgs) -> Result<(), Error> {
+    let res = read_compiled(args.file)?;
+
+    log!("Decompiling...");
+    write_output(args.output, decompile(&res).as_bytes())
+}
+
+/// Format each file in place, or stdin to stdout
+///
+/// With `--check`, lists the files that would change and
+/// fails if there are any, without touching them.
+fn fmt_main(args: FmtArgs, cwd: Option<&Path>) -> Result<(), Error> {
+    if args.files.is_empty() {
+        let data = io::read_to_string(io::stdin())?;
+        let path = match cwd {
+            Some(cwd) => cwd.join("<stdin>"),
+            None => std::env::current_dir()?.join("<stdin>"),
+        };
+
+        let res = format_source(&data, args.imports.parser(path.clone(), cwd))?;
+        if args.check {
+            return match res == data {
+                true => Ok(()),
+                false => Err(Unformatted(vec![path]).into()),
+            };
+        }
+
+        return write_output(None, res.as_bytes());
+    }
+
+    let mut unformatted = vec![];
+    for file in args.files {
+        let path = match cwd {
+            Some(cwd) => cwd.join(&file),
+            None => PathBuf::from(&file),
+        };
+
+        log!("Formatting {}...", path.display());
+        let parser = args.imports.parser(path.clone(), cwd);
+        if format_file(parser, args.check)? && args.check {
+            unformatted.push(path);
+        }
+    }
+
+    if !unformatted.is_empty() {
+        return Err(Unformatted(unformatted).into());
+    }
+
+    log!("Done!");
+    Ok(())
+}
+
+/// Load a source or compiled file, then play through it
+/// using stdin, which is why there's no reading from stdin
+/// for the file itself
+fn play_main(args: PlayArgs, cwd: Option<&Path>) -> Result<(), Error> {
+    let res = load_any(args.file, &args.imports, cwd)?;
+
+    let stdin = io::stdin().lock();
+    let entry = match args.entry {
+        Some(entry) => entry,
+        None => match pick_entry(&res, stdin, io::stdout())? {
+            Some(entry) => entry,
+            None => return Ok(()),
+        },
+    };
+
+    play(&res, &entry, io::stdin().lock(), io::stdout())
+}
+
+/// Run every transcript test, printing a diff for each one
+/// that fails. Fails if any of them did.
+fn test_main(args: TestArgs, cwd: Option<&Path>) -> Result<(), Error> {
+    let res = load_any(args.file, &args.imports, cwd)?;
+
+    let mut failed = 0;
+    let mut total = 0;
+
+    for file in args.transcripts {
+        let path = PathBuf::from(file);
+        let data = fs::read_to_string(&path).map_err(|e| Error::Read(path.clone(), e))?;
+
+        for test in parse_transcripts(&data, &path)? {
+            total += 1;
+
+            match run_transcript(&res, &test) {
+                Ok(()) => println!("test {} ({}) ... ok", test.entry, test.span),
+
+                Err(diff) => {
+                    failed += 1;
+                    println!("test {} ({}) ... FAILED", test.entry, test.span);
+                    println!("{}", diff);
+                }
+            }
+        }
+    }
+
+    println!("{} passed, {} failed", total - failed, failed);
+
+    match failed {
+        0 => Ok(()),
+        n => Err(Error::TestsFailed(n)),
+    }
+}
+
+/// Compile, then wait for any of the files that were read
+/// to change, and do it all over again. Errors are printed
+/// instead of returned, so this only stops when killed.
+///
+/// Files are checked by polling their modification times,
+/// which is plenty for a handful of dialogue files.
+fn watch_main(args: WatchArgs, cwd: Option<&Path>) -> Result<(), Error> {
+    let path = match cwd {
+        Some(cwd) => cwd.join(&args.file),
+        None => PathBuf::from(&args.file),
+    };
+
+    let modified = |paths: &[PathBuf]| {
+        paths
+            .iter()
+            .map(|v| fs::metadata(v).and_then(|v| v.modified()).ok())
+            .collect::<Vec<_>>()
+    };
+
+    loop {
+        log!("Compiling {}...", path.display());
+        let (res, deps) = parse_file_using(args.imports.parser(path.clone(), cwd));
+
+        let res = res.and_then(|res| args.out.compile(res)).and_then(|res| {
+            fs::write(&args.output, res).map_err(|e| Error::Write((&args.output).into(), e))
+        });
+
+        match res {
+            Ok(()) => log!("Wrote {}", args.output),
+            Err(e) => print_error(&e),
+        }
+
+        // newly imported files get picked up here, since
+        // the list is rebuilt after every compile
+        let watched = std::iter::once(path.clone())
+            .chain(deps)
+            .collect::<Vec<_>>();
+        let before = modified(&watched);
+        log!("Watching {} file(s) for changes...", watched.len());
+
+        while modified(&watched) == before {
+            thread::sleep(Duration::from_millis(args.interval));
+        }
+    }
+}
+
+/// Print an error to stderr the way `dg` does. Parse
+/// errors get the full compiler-style diagnostics, in
+/// color if stderr is a terminal and `NO_COLOR` isn't set.
+pub fn print_error(err: &Error) {
+    match err {
+        Error::Parse(ParseErrors(errors)) => {
+            let color = io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+
+            for e in errors {
+                eprintln!("{}", render_diagnostic(e, &FsSource, color));
+            }
+
+            eprintln!("Aborting due to {} error(s)", errors.len());
+        }
+
+        Error::DanglingGotos(DanglingGotos(dangling)) => {
+            for goto in dangling {
+                eprintln!("error: {}", goto);
+            }
+
+            eprintln!("Found {} goto(s) that lead nowhere", dangling.len());
+        }
+
+        Error::Unformatted(Unformatted(files)) => {
+            for file in files {
+                eprintln!("not formatted: {}", file.display());
+            }
+
+            eprintln!("{} file(s) need formatting", files.len());
+        }
+
+        e => {
+            eprintln!("Error: {}", e);
+
+            let mut source = std::error::Error::source(e);
+            while let Some(inner) = source {
+                eprintln!("Caused by: {}", inner);
+                source = inner.source();
+            }
+        }
+    }
+}
+
+/// Load either a compiled or `.dg` file, going by the extension
+fn load_any(
+    file: String,
+    imports: &ImportArgs,
+    cwd: Option<&Path>,
+) -> Result<InteractionMap, Error> {
+    match OutputFormat::from_extension(&file) {
+        Some(_) => read_compiled(Some(file)),
+        None => parse_input(Some(file), imports, cwd),
+    }
+}
+
+/// Read and deserialize a compiled file, or stdin if there is
+/// none. The format goes by the extension, and stdin is bincode.
+fn read_compiled(file: Option<String>) -> Result<InteractionMap, Error> {
+    let format = file
+        .as_deref()
+        .and_then(OutputFormat::from_extension)
+        .unwrap_or_default();
+
+    log!("Reading...");
+    let data = match file {
+        Some(file) => {
+            let path = PathBuf::from(file);
+            fs::read(&path).map_err(|e| Error::Read(path, e))?
+        }
+
+        None => {
+            let mut data = vec![];
+            io::stdin().read_to_end(&mut data)?;
+            data
+        }
+    };
+
+    deserialize_as(&data, format)
+}
+
+/// Write to the output file, or stdout if there is none
+///
+/// Only called once there's something to write, so a failed
+/// compile doesn't leave behind an empty output file.
+fn write_output(file: Option<String>, data: &[u8]) -> Result<(), Error> {
+    match file {
+        Some(file) => {
+            let path = PathBuf::from(file);
+            File::create(&path)
+                .and_then(|mut f| f.write_all(data))
+                .map_err(|e| Error::Write(path, e))
+        }
+
+        None => Ok(io::stdout().write_all(data)?),
+    }
+}
+
+/// The input file as it was already read, with anything
+/// it imports still coming off the disk
+struct EntrySource {
+    path: PathBuf,
+    data: String,
+}
+
+impl SourceProvider for EntrySource {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        match path == self.path {
+            true => Ok(self.data.clone()),
+            false => FsSource.read(path),
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path == self.path || FsSource.exists(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> PathBuf {
+        FsSource.canonicalize(path)
+    }
+}
+
+/// Read and parse the input file, or stdin if there is none
+fn parse_input(
+    file: Option<String>,
+    imports: &ImportArgs,
+    cwd: Option<&Path>,
+) -> Result<InteractionMap, Error> {
+    let parser = input_parser(file, imports, cwd)?;
+
+    // report every error at once instead of making
+    // the user recompile after fixing each one
+    log!("Parsing...");
+    parse_file_using(parser).0
+}
+
+/// Read the input file, or stdin if there is none, and
+/// set up a parser for it
+fn input_parser(
+    file: Option<String>,
+    imports: &ImportArgs,
+    cwd: Option<&Path>,
+) -> Result<DgParser, Error> {
+    log!("Reading...");
+    let data = match file {
+        Some(ref file) => {
+            let path = PathBuf::from(file);
+            let read = File::open(&path).and_then(io::read_to_string);
+            read.map_err(|e| Error::Read(path, e))?
+        }
+
+        None => io::read_to_string(io::stdin())?,
+    };
+
+    // imports are resolved relative to the folder `path` is in,
+    // so stdin pretends to be a file in the current dir. if the
+    // cwd argument is passed in, it replaces that folder.
+    let name = file
+        .as_deref()
+        .and_then(|file| Path::new(file).file_name())
+        .unwrap_or("<stdin>".as_ref());
+
+    let path = match (cwd, file.as_deref()) {
+        (Some(cwd), _) => cwd.join(name),
+        (None, Some(file)) => PathBuf::from(file),
+        (None, None) => std::env::current_dir()?.join(name),
+    };
+
+    let source = EntrySource {
+        path: path.clone(),
+        data,
+    };
+
+    Ok(imports.parser(path, cwd).sources(source))
+}
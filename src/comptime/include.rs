@@ -2,47 +2,341 @@
 //! Type definitions for the `Execute` and `Import` directives.
 //!
 //! IMPORTANT: `Execute` is NOT for importing interactions from
-//! other files! It just executes the contents of the file as-is
-//! in a separate execution environment. If you want to import
-//! interactions from another file, PLEASE use the `Import`
-//! directive instead.
+//! other files! It just runs the file as a comptime script, as
+//! if its lines were pasted in where the `Execute` is. If you
+//! want to import interactions from another file, PLEASE use
+//! the `Import` directive instead.
 //!
-//! TODO unit test if that claim is actually true ^^^ lol
+//! Anything the executed script does to the parser's state
+//! sticks around afterwards, so its `Echo`s show up in the
+//! logs and its `Link`s and `Unlink`s apply to the rest of the
+//! file. The only things it doesn't share are its own place in
+//! the script, so a `Quit` in there only stops that script,
+//! and its path, so any `Execute` or `Import` inside it is
+//! relative to the executed file instead of the caller.
 //!
+//! Paths are looked up relative to the folder of the file the
+//! directive is in, then in each include dir the parser was
+//! given, in order. Paths starting with `@/` are only looked
+//! up relative to the project root instead, which is the
+//! entry file's folder unless the parser was told otherwise.
+//!
+//! ```text
+//! Import @/npcs/shopkeepers.dg
+//! ```
+//!
+//! `Import` can also put everything from a file under a
+//! namespace, so its IDs don't clash with anyone else's:
+//!
+//! ```text
+//! Import shop.dg as Shop
+//! // `% Greeting` in shop.dg is now `Shop::Greeting`
+//! ```
+//!
+//! ...or only take some of the interactions from it. A `*` after
+//! an ID takes everything it can goto from there, too.
+//!
+//! ```text
+//! Import Greeting*, Farewell from npc/shopkeeper.dg
+//! ```
+//!
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use super::{Result, Script, ScriptErrorKind};
+use crate::consts::NAMESPACE_SEPARATOR;
+use crate::parser::{DgParser, ScriptContext};
+use crate::{InteractionMap, Label, SourceProvider};
+
+/// An interaction picked out by `Import ... from`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Selection {
+    pub id: String,
+
+    /// Also take everything it can goto, and so on
+    pub follow_gotos: bool,
+}
+
+/// Everything about an `Import` besides the file itself
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportOptions {
+    /// Put every ID under this, like `Shop::Greeting`
+    pub namespace: Option<String>,
+
+    /// Only take these interactions, instead of all of them
+    pub only: Option<Vec<Selection>>,
+}
+
+impl ImportOptions {
+    /// Split up `Greeting*, Farewell from shop.dg as Shop`,
+    /// giving back the path and everything else
+    pub fn parse(args: &str) -> Result<(&str, Self)> {
+        let mut res = Self::default();
+
+        let args = match args.rsplit_once(" as ") {
+            Some((args, namespace)) => {
+                let valid = !namespace.is_empty() && !namespace.contains(char::is_whitespace);
+                if !valid {
+                    return Err(ScriptErrorKind::InvalidNamespace(namespace.to_owned()));
+                }
 
-use std::fs::File;
-use std::io;
-use std::path::PathBuf;
+                res.namespace = Some(namespace.to_owned());
+                args
+            }
 
-use super::{Result, ScriptError};
-use crate::parser::DgParser;
-use crate::{InteractionMap, ParseResult};
+            None => args,
+        };
+
+        let Some((ids, path)) = args.split_once(" from ") else {
+            return Ok((args, res));
+        };
+
+        let only = ids
+            .split(',')
+            .map(|id| {
+                let id = id.trim();
+                let (id, follow_gotos) = match id.strip_suffix('*') {
+                    Some(id) => (id.trim_end(), true),
+                    None => (id, false),
+                };
+
+                match id.is_empty() {
+                    true => Err(ScriptErrorKind::InvalidImport),
+                    false => Ok(Selection {
+                        id: id.to_owned(),
+                        follow_gotos,
+                    }),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        res.only = Some(only);
+        Ok((path, res))
+    }
+}
 
 /// Used for `Execute` and `Import` directives.
 #[derive(Clone, Debug)]
 pub struct ScriptPath(pub PathBuf);
 
 impl ScriptPath {
-    /// Create new ScriptPath by resolving one and appending
-    /// a new path onto it
-    pub fn make_append(&self, path: PathBuf) -> Self {
-        Self(self.0.parent().unwrap().join(path))
+    /// Find the file a directive is talking about, going through
+    /// every place it could be until one of them exists.
+    ///
+    /// If none of them do, they all get added to `out`'s
+    /// dependencies, so creating any of them counts as a change.
+    pub fn resolve(&self, path: &Path, out: &mut ScriptContext) -> Result<Self> {
+        let dir = self.0.parent().unwrap_or(Path::new(""));
+
+        let tried = match path.strip_prefix("@") {
+            Ok(path) => {
+                let root = out.root.as_deref().unwrap_or(dir);
+                vec![root.join(path)]
+            }
+
+            Err(_) => {
+                let dirs =
+                    std::iter::once(dir).chain(out.include_dirs.iter().map(PathBuf::as_path));
+                let mut res: Vec<PathBuf> = vec![];
+
+                // absolute paths come out the same from every dir
+                for candidate in dirs.map(|v| v.join(path)) {
+                    if !res.contains(&candidate) {
+                        res.push(candidate);
+                    }
+                }
+
+                res
+            }
+        };
+
+        if let Some(found) = tried.iter().find(|v| out.sources.exists(v)) {
+            return Ok(Self(found.clone()));
+        }
+
+        for path in &tried {
+            out.depend(path);
+        }
+
+        Err(ScriptErrorKind::NotFound(path.to_owned(), tried.into()))
+    }
+
+    /// Error if the path is already being imported or executed
+    /// further up the chain, since it'd never stop otherwise.
+    /// Gives back the canonical path if it's fine.
+    fn check_cycle(&self, out: &ScriptContext) -> Result<PathBuf> {
+        let target = out.sources.canonicalize(&self.0);
+
+        match out.import_chain.iter().position(|v| *v == target) {
+            Some(start) => {
+                let mut cycle = out.import_chain[start..].to_vec();
+                cycle.push(target);
+                Err(ScriptErrorKind::ImportCycle(cycle))
+            }
+
+            None => Ok(target),
+        }
+    }
+
+    /// Run the script at the path, sharing `out` with the
+    /// caller. Used by the `Execute` directive.
+    pub fn execute(&self, out: &mut ScriptContext) -> Result<()> {
+        out.depend(&self.0);
+        let target = self.check_cycle(out)?;
+        let content = self.read(&*out.sources)?;
+
+        out.import_chain.push(target);
+        let res = Script::new(content, self.clone()).execute(out);
+        out.import_chain.pop();
+
+        res.map_err(|e| ScriptErrorKind::Execute(self.0.clone(), Box::new(e)))
     }
 
     /// Get the contents of the script at the path.
-    /// Used by the `Execute` directive.
-    pub fn read(&self) -> Result<String> {
-        File::open(&self.0)
-            .and_then(|file| io::read_to_string(file))
-            .map_err(|_| ScriptError::FileOpen(self.0.clone()))
+    pub fn read(&self, sources: &dyn SourceProvider) -> Result<String> {
+        sources
+            .read(&self.0)
+            .map_err(|_| ScriptErrorKind::FileOpen(self.0.clone()))
     }
 
     /// Run a second parser instance on the script at the path.
     /// Used by the `Import` directive.
-    pub fn parse_import(&self) -> ParseResult<InteractionMap> {
-        let contents = self.read()?;
+    ///
+    /// The file, and anything it depends on, gets added to
+    /// `out`'s dependencies, even if parsing fails.
+    ///
+    /// Importing a file that's already being imported further
+    /// up the chain is an error. Importing one that was already
+    /// imported some other way gives back nothing, since its
+    /// interactions are already in there.
+    ///
+    /// With a namespace, every ID from the file gets put under
+    /// it, like `Shop::Greeting`, along with any gotos in there
+    /// that lead to them. Namespaced and selective imports don't
+    /// count as the same file as a plain import.
+    ///
+    /// Getting the same interaction from the same file twice
+    /// (through different imports) is fine, but getting one
+    /// with the same ID from anywhere else is an error.
+    pub fn parse_import(
+        &self,
+        out: &mut ScriptContext,
+        options: &ImportOptions,
+    ) -> Result<InteractionMap> {
+        out.depend(&self.0);
+
+        let target = self.check_cycle(out)?;
+
+        let whole_file = *options == ImportOptions::default();
+        let imported = match whole_file {
+            false => HashSet::new(),
+            true if !out.imported.insert(target) => return Ok(InteractionMap::new()),
+            true => out.imported.clone(),
+        };
+
+        let contents = self.read(&*out.sources)?;
+
+        let mut parser = DgParser::imported_from(self.0.clone(), out, imported);
+        let res = parser.parse_all(&contents);
+
+        for dep in parser.dependencies() {
+            out.depend(&dep);
+        }
+
+        if whole_file {
+            out.imported.extend(parser.imported().iter().cloned());
+        }
+
+        let res = res.map_err(|e| ScriptErrorKind::Import(self.0.clone(), Box::new(e)))?;
+        let ids = res.keys().cloned().collect::<HashSet<_>>();
+
+        let selected = match options.only {
+            Some(ref only) => selected(res, only).map_err(ScriptErrorKind::NoSuchInteraction)?,
+            None => res,
+        };
+
+        let mut res = InteractionMap::new();
+        for (local, mut ix) in selected {
+            let id = match options.namespace {
+                Some(ref namespace) => {
+                    for label in ix.ending.labels_mut() {
+                        let Label::Goto(ref mut goto) = label;
+                        if ids.contains(goto) {
+                            *goto = namespaced(namespace, goto);
+                        }
+                    }
+
+                    namespaced(namespace, &local)
+                }
+
+                None => local.clone(),
+            };
 
-        let mut parser = DgParser::new(self.0.clone());
-        parser.parse_all(&contents)
+            let origin = parser.origins().get(&local).unwrap_or(&self.0).clone();
+            match out.origins.get(&id) {
+                None => {
+                    out.origins.insert(id.clone(), origin);
+                }
+
+                Some(first)
+                    if out.sources.canonicalize(first) == out.sources.canonicalize(&origin) => {}
+
+                Some(first) => {
+                    let files = Box::new([first.clone(), origin]);
+                    return Err(ScriptErrorKind::DuplicateIX(id, files));
+                }
+            }
+
+            res.insert(id, ix);
+        }
+
+        Ok(res)
     }
 }
+
+/// `id` as it'd be under `namespace`
+fn namespaced(namespace: &str, id: &str) -> String {
+    format!("{}{}{}", namespace, NAMESPACE_SEPARATOR, id)
+}
+
+/// Only the interactions in `only`, and whatever they goto if
+/// they're supposed to be followed. `Err` with the ID of the
+/// first one that isn't in `map`.
+fn selected(
+    map: InteractionMap,
+    only: &[Selection],
+) -> std::result::Result<InteractionMap, String> {
+    let mut take = HashSet::new();
+
+    for selection in only {
+        if !map.contains_key(&selection.id) {
+            return Err(selection.id.clone());
+        }
+
+        let mut seen = HashSet::new();
+        let mut stack = vec![selection.id.as_str()];
+
+        while let Some(id) = stack.pop() {
+            // gotos that lead outside the file get skipped over
+            let Some(ix) = map.get(id) else {
+                continue;
+            };
+
+            if !seen.insert(id) {
+                continue;
+            }
+
+            take.insert(id.to_owned());
+            if selection.follow_gotos {
+                let labels = ix.ending.labels().into_iter();
+                stack.extend(labels.map(|(_, Label::Goto(id))| id.as_str()));
+            }
+        }
+    }
+
+    Ok(map
+        .into_iter()
+        .filter(|(id, _)| take.contains(id))
+        .collect())
+}
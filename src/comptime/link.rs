@@ -1,18 +1,18 @@
 use std::fmt;
 use std::ops::Deref;
 
-use super::ScriptError;
+use super::ScriptErrorKind;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct LinkKVPair((String, String));
 
 impl LinkKVPair {
     /// consume one line of split up words
-    pub fn from_words<'a, I>(split: &mut I) -> Result<Self, ScriptError>
+    pub fn from_words<'a, I>(split: &mut I) -> Result<Self, ScriptErrorKind>
     where
         I: Iterator<Item = &'a str>,
     {
-        let property = split.next().ok_or(ScriptError::InvalidLink)?.to_owned();
+        let property = split.next().ok_or(ScriptErrorKind::InvalidLink)?.to_owned();
         Ok(LinkKVPair((property, split.collect::<Vec<_>>().join(" "))))
     }
 
@@ -7,31 +7,60 @@
 //!
 
 use std::cell::RefCell;
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::str::SplitWhitespace;
 use thiserror::Error;
 
 use crate::consts::PREFIX_COMMENT;
-use crate::pages::ParseError;
+use crate::pages::{ParseError, Span};
 use crate::parser::ScriptContext;
 use crate::Interaction;
 
 mod include;
 mod link;
 
-pub use include::ScriptPath;
+pub use include::{ImportOptions, ScriptPath};
 pub use link::{Link, LinkKVPair, Unlink};
 
-pub type Result<T> = std::result::Result<T, ScriptError>;
+pub type Result<T> = std::result::Result<T, ScriptErrorKind>;
+
+/// A `ScriptErrorKind` along with where it happened
+#[derive(Debug, PartialEq)]
+pub struct ScriptError {
+    pub kind: ScriptErrorKind,
+    pub span: Span,
+}
+
+impl ScriptError {
+    pub fn new(kind: ScriptErrorKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.kind)
+    }
+}
+
+impl std::error::Error for ScriptError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.kind.source()
+    }
+}
 
 #[derive(Error, Debug, PartialEq)]
-pub enum ScriptError {
+pub enum ScriptErrorKind {
     #[error("No such command")]
     NoSuchCommand,
 
     #[error("Incorrect usage of Link directive")]
     InvalidLink,
 
+    #[error("Incorrect usage of Import directive")]
+    InvalidImport,
+
     #[error("Attempt to link 2 of the same property in associations")]
     DoubleLink,
 
@@ -41,8 +70,48 @@ pub enum ScriptError {
     #[error("Could not open file at path {0}")]
     FileOpen(PathBuf),
 
+    /// The path as written, and everywhere it was looked for
+    #[error("Could not find {} in any of: {}", .0.display(), display_list(.1))]
+    NotFound(PathBuf, Box<[PathBuf]>),
+
     #[error("Error while importing interactions from script at path {0}")]
     Import(PathBuf, #[source] Box<ParseError>),
+
+    #[error("Error while executing script at path {0}")]
+    Execute(PathBuf, #[source] Box<ScriptError>),
+
+    /// The ID, then the file it was first defined in and
+    /// the one that tried to define it again (boxed, since
+    /// they'd make every other error take up more space)
+    #[error(
+        "Interaction {0} from {} is already defined in {}",
+        .1[1].display(),
+        .1[0].display()
+    )]
+    DuplicateIX(String, Box<[PathBuf; 2]>),
+
+    /// Only the ID, since the span already points
+    /// at the `Import` with the file in it
+    #[error("The imported file has no interaction {0}")]
+    NoSuchInteraction(String),
+
+    #[error("{0} is not a valid namespace")]
+    InvalidNamespace(String),
+
+    /// Every file in the cycle, starting and ending
+    /// with the same one
+    #[error("Files import or execute each other in a cycle: {}", display_chain(.0))]
+    ImportCycle(Vec<PathBuf>),
+}
+
+fn display_chain(paths: &[PathBuf]) -> String {
+    let paths = paths.iter().map(|v| v.display().to_string());
+    paths.collect::<Vec<_>>().join(" -> ")
+}
+
+fn display_list(paths: &[PathBuf]) -> String {
+    let paths = paths.iter().map(|v| v.display().to_string());
+    paths.collect::<Vec<_>>().join(", ")
 }
 
 #[derive(Clone, Debug, Default)]
@@ -62,6 +131,9 @@ pub struct Script {
     content: String,
     state: RefCell<ComptimeState>,
     path: ScriptPath,
+
+    /// Line number of the file this script's content starts after
+    line_offset: usize,
 }
 
 // stuff passed back to the parser once the script is done
@@ -70,6 +142,9 @@ pub enum ScriptOutput {
     LogMessage(String),
     Link(Link),
     Interaction(String, Interaction),
+
+    /// A file that was read by `Import` or `Execute`
+    Dependency(PathBuf),
 }
 
 impl Script {
@@ -78,9 +153,17 @@ impl Script {
             content,
             state: RefCell::new(ComptimeState::default()),
             path,
+            line_offset: 0,
         }
     }
 
+    /// Number lines in error spans as if the script's
+    /// content started right after line `line` of its file
+    pub fn starting_at(mut self, line: usize) -> Self {
+        self.line_offset = line;
+        self
+    }
+
     /// returns the new state (`None` = no change)
     fn execute_normal(&self, line: &str, out: &mut ScriptContext) -> Result<Option<ComptimeState>> {
         if line.starts_with(PREFIX_COMMENT) {
@@ -94,11 +177,13 @@ impl Script {
         };
 
         // join the iterator into a script path
-        fn script_path(script: &Script, split: SplitWhitespace) -> ScriptPath {
+        fn script_path(
+            script: &Script,
+            split: SplitWhitespace,
+            out: &mut ScriptContext,
+        ) -> Result<ScriptPath> {
             let args = split.collect::<Vec<_>>().join(" ");
-            let path = PathBuf::from(args);
-
-            script.path.make_append(path)
+            script.path.resolve(Path::new(&args), out)
         }
 
         match command {
@@ -122,34 +207,35 @@ impl Script {
             }
 
             "Import" => {
+                // counts even if the import fails, so a bad one
+                // doesn't also make its interaction look empty
+                out.imported_since_push = true;
+
                 // Isolates the returned links from any other
                 // side effects the script might have.
                 // Might need to do more than just this
                 // later on when the language has more features.
-                let path = script_path(self, split);
-                let interactions = path
-                    .parse_import()
-                    .map_err(|e| ScriptError::Import(path.0, Box::new(e)))?;
+                let args = split.collect::<Vec<_>>().join(" ");
+                let (path, options) = ImportOptions::parse(&args)?;
+                let path = self.path.resolve(Path::new(path), out)?;
+                let interactions = path.parse_import(out, &options)?;
 
                 let mapped = interactions
                     .into_iter()
                     .map(|(id, v)| ScriptOutput::Interaction(id, v));
 
-                out.0.extend(mapped);
+                out.outputs.extend(mapped);
             }
 
             "Execute" => {
-                // TODO this probably isn't doing what it should
-                let path = script_path(self, split);
-                let content = path.read()?;
-                let mut script = Self::new(content, path);
-                script.execute(out)?;
+                let path = script_path(self, split, out)?;
+                path.execute(out)?;
             }
 
             "Quit" => return Ok(Some(ComptimeState::Quit)),
 
             _ => {
-                return Err(ScriptError::NoSuchCommand);
+                return Err(ScriptErrorKind::NoSuchCommand);
             }
         };
 
@@ -178,7 +264,7 @@ impl Script {
         // links that have the same `from` and remove the
         // `linked` properties they have in common with
         // the unlink we've just built
-        out.unlink(&unlink);
+        out.unlink(unlink);
 
         Ok(Some(ComptimeState::Normal))
     }
@@ -206,7 +292,7 @@ impl Script {
         // <https://github.com/Lamby777/dialogical/issues/2>
         let is_dupe = out.iter_links().any(|v| v.target == link.target);
         if is_dupe {
-            return Err(ScriptError::DoubleLink);
+            return Err(ScriptErrorKind::DoubleLink);
         }
 
         // we're done building the link, so...
@@ -215,21 +301,27 @@ impl Script {
         Ok(Some(ComptimeState::Normal))
     }
 
-    pub fn execute(&mut self, out: &mut ScriptContext) -> Result<()> {
+    pub fn execute(&mut self, out: &mut ScriptContext) -> std::result::Result<(), ScriptError> {
         use ComptimeState::*;
         let lines = self.content.lines().chain(std::iter::once(""));
 
         // take one line at a time...
         // remembers which mode we're in
 
-        for line in lines {
+        for (i, raw) in lines.enumerate() {
+            let line = raw.trim();
             let new_state = match *self.state.borrow_mut() {
-                Normal => self.execute_normal(line, out)?,
-                Link(ref mut link) => self.execute_link(line, out, link)?,
-                Unlink(ref mut unlink) => self.execute_unlink(line, out, unlink)?,
+                Normal => self.execute_normal(line, out),
+                Link(ref mut link) => self.execute_link(line, out, link),
+                Unlink(ref mut unlink) => self.execute_unlink(line, out, unlink),
 
                 Quit => unreachable!(),
-            };
+            }
+            .map_err(|kind| {
+                let col = raw.chars().take_while(|c| c.is_whitespace()).count() + 1;
+                let span = Span::new(self.path.0.clone(), self.line_offset + i + 1, col);
+                ScriptError::new(kind, span)
+            })?;
 
             match new_state {
                 Some(Quit) => {
@@ -1,5 +1,9 @@
 use super::*;
+use crate::source::Sources;
+use crate::MemorySource;
+
 use pretty_assertions::assert_eq;
+use std::sync::Arc;
 
 macro_rules! comptime {
     ($code:expr) => {{
@@ -8,6 +12,17 @@ macro_rules! comptime {
         let res = Script::new($code.into(), path).execute(&mut out);
         (res, out)
     }};
+
+    // run as if it was in `main.dg`, next to the files in `$sources`
+    ($code:expr, $sources:expr) => {{
+        let mut out = ScriptContext::default();
+        out.sources = Sources(Arc::new($sources));
+        out.import_chain.push("main.dg".into());
+
+        let path = ScriptPath("main.dg".into());
+        let res = Script::new($code.into(), path).execute(&mut out);
+        (res, out)
+    }};
 }
 
 #[test]
@@ -42,3 +57,144 @@ fn hello_world() {
     assert!(res.is_ok());
     assert_eq!(out.logs(), vec!["Hello, world!".to_string()]);
 }
+
+#[test]
+fn error_span() {
+    let (res, _) = comptime!("Echo hi\n\n    Bogus command");
+    let err = res.unwrap_err();
+
+    assert_eq!(err.kind, ScriptErrorKind::NoSuchCommand);
+    assert_eq!(err.span, Span::new("irrelevant".into(), 3, 5));
+}
+
+#[test]
+fn import_options() {
+    let parse = |args| ImportOptions::parse(args).map(|(path, v)| (path.to_owned(), v));
+    let select = |id: &str, follow_gotos| include::Selection {
+        id: id.to_owned(),
+        follow_gotos,
+    };
+
+    assert_eq!(
+        parse("shop.dg"),
+        Ok(("shop.dg".to_owned(), ImportOptions::default()))
+    );
+
+    assert_eq!(
+        parse("Greeting*, Rodrick Sign #1 from npc/shop keeper.dg as Shop"),
+        Ok((
+            "npc/shop keeper.dg".to_owned(),
+            ImportOptions {
+                namespace: Some("Shop".to_owned()),
+                only: Some(vec![
+                    select("Greeting", true),
+                    select("Rodrick Sign #1", false)
+                ]),
+            }
+        ))
+    );
+
+    assert_eq!(
+        parse("A, , B from shop.dg"),
+        Err(ScriptErrorKind::InvalidImport)
+    );
+    assert_eq!(
+        parse("shop.dg as Two Words"),
+        Err(ScriptErrorKind::InvalidNamespace("Two Words".to_owned()))
+    );
+}
+
+#[test]
+fn execute_shares_logs_and_links() {
+    let sources =
+        MemorySource::new().with("links.dgs", "Echo in links\nLink NAME Cherry\nVOX Mira");
+    let (res, out) = comptime!("Execute links.dgs\nEcho back in main", sources);
+
+    assert_eq!(res, Ok(()));
+    assert_eq!(out.logs(), vec!["in links", "back in main"]);
+    assert_eq!(out.links().len(), 1);
+    assert_eq!(
+        out.links()[0].target,
+        LinkKVPair::from_tuple(("NAME", "Cherry"))
+    );
+}
+
+#[test]
+fn execute_unlinks_callers_links() {
+    let sources = MemorySource::new().with("unlink.dgs", "Unlink NAME Cherry\nVOX");
+    let (res, out) = comptime!("Link NAME Cherry\nVOX Mira\n\nExecute unlink.dgs", sources);
+
+    assert_eq!(res, Ok(()));
+    assert!(out.links().is_empty());
+}
+
+#[test]
+fn quit_only_leaves_executed_script() {
+    let sources = MemorySource::new().with("quit.dgs", "Echo before\nQuit\nEcho after");
+    let (res, out) = comptime!("Execute quit.dgs\nEcho still here", sources);
+
+    assert_eq!(res, Ok(()));
+    assert_eq!(out.logs(), vec!["before", "still here"]);
+}
+
+#[test]
+fn execute_relative_to_executed_file() {
+    let sources = MemorySource::new()
+        .with("scripts/outer.dgs", "Execute inner/inner.dgs")
+        .with("scripts/inner/inner.dgs", "Echo found it");
+    let (res, out) = comptime!("Execute scripts/outer.dgs", sources);
+
+    assert_eq!(res, Ok(()));
+    assert_eq!(out.logs(), vec!["found it"]);
+    assert_eq!(
+        out.dependencies(),
+        vec![
+            PathBuf::from("scripts/outer.dgs"),
+            PathBuf::from("scripts/inner/inner.dgs")
+        ]
+    );
+}
+
+#[test]
+fn execute_error_span() {
+    let sources = MemorySource::new().with("bad.dgs", "Echo fine\n  Bogus");
+    let (res, _) = comptime!("Echo hi\nExecute bad.dgs", sources);
+    let err = res.unwrap_err();
+
+    // reported at the `Execute`, with the real error inside
+    assert_eq!(err.span, Span::new("main.dg".into(), 2, 1));
+
+    let ScriptErrorKind::Execute(path, inner) = err.kind else {
+        panic!("Expected an execute error, got {:?}", err.kind);
+    };
+
+    assert_eq!(path, PathBuf::from("bad.dgs"));
+    assert_eq!(inner.kind, ScriptErrorKind::NoSuchCommand);
+    assert_eq!(inner.span, Span::new("bad.dgs".into(), 2, 3));
+}
+
+#[test]
+fn execute_cycle() {
+    let sources = MemorySource::new().with("loop.dgs", "Execute loop.dgs");
+    let (res, _) = comptime!("Execute loop.dgs", sources);
+
+    let ScriptErrorKind::Execute(_, inner) = res.unwrap_err().kind else {
+        panic!("Expected an execute error");
+    };
+
+    assert_eq!(
+        inner.kind,
+        ScriptErrorKind::ImportCycle(vec!["loop.dgs".into(), "loop.dgs".into()])
+    );
+}
+
+#[test]
+fn execute_missing_file() {
+    let (res, out) = comptime!("Execute nope.dgs", MemorySource::new());
+
+    assert_eq!(
+        res.unwrap_err().kind,
+        ScriptErrorKind::NotFound("nope.dgs".into(), vec!["nope.dgs".into()].into())
+    );
+    assert_eq!(out.dependencies(), vec![PathBuf::from("nope.dgs")]);
+}
@@ -7,3 +7,6 @@ pub const PREFIX_COMMENT: &str = "//";
 // interaction ending stuff
 pub const PREFIX_CHOICE: char = '>';
 pub const PREFIX_GOTO_LABEL: char = '@';
+
+// imports
+pub const NAMESPACE_SEPARATOR: &str = "::";
@@ -0,0 +1,375 @@
+//!
+//! The `.dgc` container format.
+//!
+//! ```text
+//! "DGC\0"     magic bytes
+//! u16 (LE)    format version
+//! Header      bincode, see below
+//! payload     bincode of the interactions, sorted by ID
+//! ```
+//!
+//! Files from before the container existed are just the
+//! payload, with no magic in front. Those still load, as
+//! format version 0.
+//!
+
+use std::collections::BTreeMap;
+
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::dump::sorted;
+use crate::{Error, Interaction, InteractionMap};
+
+pub const MAGIC: &[u8; 4] = b"DGC\0";
+
+/// Bump this whenever the payload layout changes, and
+/// teach `unpack` how to read the old one if possible.
+pub const FORMAT_VERSION: u16 = 1;
+
+/// Everything in a `.dgc` besides the interactions themselves
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Header {
+    /// Version of `dialogical` that compiled the file
+    pub compiler_version: String,
+
+    /// FNV-1a hash of the rest of the header, then the payload
+    pub hash: u64,
+
+    /// Where each interaction's bytes are in the payload,
+    /// for loading them one at a time
+    pub toc: Option<Vec<TocEntry>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TocEntry {
+    pub id: String,
+    pub offset: u64,
+    pub len: u64,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ContainerError {
+    #[error("Not a .dgc file, or one too corrupted to tell")]
+    NotDgc,
+
+    #[error("File ends before the .dgc header does")]
+    Truncated,
+
+    #[error("Compiled with format version {found}, but only versions up to {supported} are supported. Try updating dialogical.")]
+    UnsupportedVersion { found: u16, supported: u16 },
+
+    #[error("Contents don't match the hash in the header, so the file is probably corrupted")]
+    HashMismatch { expected: u64, found: u64 },
+
+    #[error(
+        "Table of contents points outside the file for `{0}`, so the file is probably corrupted"
+    )]
+    BadTocEntry(String),
+}
+
+/// 64-bit FNV-1a. `DefaultHasher` isn't guaranteed to give
+/// the same results between Rust versions, so it can't be
+/// used for something that ends up in a file.
+fn fnv1a(hash: u64, data: &[u8]) -> u64 {
+    data.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Hash of everything in the header except the hash itself,
+/// followed by the payload, so a flipped bit in the table of
+/// contents gets caught too
+fn checksum(header: &Header, payload: &[u8]) -> Result<u64, Error> {
+    let meta = bincode::serialize(&(&header.compiler_version, &header.toc))
+        .map_err(|e| Error::Serialize(e))?;
+
+    Ok(fnv1a(fnv1a(0xcbf29ce484222325, &meta), payload))
+}
+
+/// Byte ranges of each interaction in the bincode of `map`
+fn make_toc(map: &BTreeMap<&str, &Interaction>) -> Result<Vec<TocEntry>, Error> {
+    fn size(v: &impl Serialize) -> Result<u64, Error> {
+        bincode::serialized_size(v).map_err(|e| Error::Serialize(e))
+    }
+
+    // bincode puts the length of the map first
+    let mut offset = size(&(map.len() as u64))?;
+    let mut res = vec![];
+
+    for (id, ix) in map {
+        offset += size(id)?;
+        let len = size(ix)?;
+
+        res.push(TocEntry {
+            id: id.to_string(),
+            offset,
+            len,
+        });
+
+        offset += len;
+    }
+
+    Ok(res)
+}
+
+/// Serialize interactions into a `.dgc`, optionally
+/// with a table of contents
+pub fn pack(map: &InteractionMap, toc: bool) -> Result<Vec<u8>, Error> {
+    let map = sorted(map);
+    let payload = bincode::serialize(&map).map_err(|e| Error::Serialize(e))?;
+
+    let mut header = Header {
+        compiler_version: env!("CARGO_PKG_VERSION").to_owned(),
+        hash: 0,
+        toc: toc.then(|| make_toc(&map)).transpose()?,
+    };
+    header.hash = checksum(&header, &payload)?;
+
+    let mut res = MAGIC.to_vec();
+    res.extend(FORMAT_VERSION.to_le_bytes());
+    bincode::serialize_into(&mut res, &header).map_err(|e| Error::Serialize(e))?;
+    res.extend(payload);
+
+    Ok(res)
+}
+
+/// Split a `.dgc` into its header and payload, checking
+/// the version and hash along the way.
+///
+/// Files without a header get `None`, but only if they
+/// really are interactions from before headers existed.
+pub fn unpack(data: &[u8]) -> Result<(Option<Header>, &[u8]), Error> {
+    let Some(rest) = data.strip_prefix(MAGIC) else {
+        // version 0, from before there was a header. the payload
+        // was the same, except not sorted, which doesn't matter.
+        // anything else without the magic is some other file.
+        return match bincode::deserialize::<InteractionMap>(data) {
+            Ok(_) => Ok((None, data)),
+            Err(_) => Err(ContainerError::NotDgc.into()),
+        };
+    };
+
+    let (version, mut rest) = match rest {
+        [a, b, rest @ ..] => (u16::from_le_bytes([*a, *b]), rest),
+        _ => return Err(ContainerError::Truncated.into()),
+    };
+
+    if version > FORMAT_VERSION {
+        return Err(ContainerError::UnsupportedVersion {
+            found: version,
+            supported: FORMAT_VERSION,
+        }
+        .into());
+    }
+
+    // same settings as `bincode::serialize`, but a length prefix
+    // can't ask for more bytes than there are left in the file
+    let header: Header = bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(rest.len() as u64)
+        .deserialize_from(&mut rest)
+        .map_err(|_| ContainerError::Truncated)?;
+
+    let found = checksum(&header, rest)?;
+    if found != header.hash {
+        return Err(ContainerError::HashMismatch {
+            expected: header.hash,
+            found,
+        }
+        .into());
+    }
+
+    Ok((Some(header), rest))
+}
+
+/// Read just the header of a `.dgc`, or `None` if it's from
+/// before headers existed
+pub fn read_header(data: &[u8]) -> Result<Option<Header>, Error> {
+    unpack(data).map(|(header, _)| header)
+}
+
+/// Load a single interaction out of a `.dgc`, using the table of
+/// contents if it has one, or loading everything if it doesn't.
+pub fn deserialize_interaction(data: &[u8], id: &str) -> Result<Option<Interaction>, Error> {
+    let (header, payload) = unpack(data)?;
+
+    let Some(toc) = header.and_then(|v| v.toc) else {
+        let mut map: InteractionMap =
+            bincode::deserialize(payload).map_err(|e| Error::Deserialize(e))?;
+        return Ok(map.remove(id));
+    };
+
+    let Some(entry) = toc.iter().find(|v| v.id == id) else {
+        return Ok(None);
+    };
+
+    let bytes = entry
+        .offset
+        .checked_add(entry.len)
+        .and_then(|end| {
+            Some((
+                usize::try_from(entry.offset).ok()?,
+                usize::try_from(end).ok()?,
+            ))
+        })
+        .and_then(|(start, end)| payload.get(start..end))
+        .ok_or_else(|| ContainerError::BadTocEntry(entry.id.clone()))?;
+
+    bincode::deserialize(bytes)
+        .map(Some)
+        .map_err(|e| Error::Deserialize(e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deserialize;
+    use crate::test_util::parse_dummy;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn round_trip() {
+        let map = parse_dummy("rodrick.dg");
+
+        for toc in [false, true] {
+            let packed = pack(&map, toc).unwrap();
+            assert!(packed.starts_with(MAGIC));
+            assert_eq!(deserialize(&packed).unwrap(), map);
+        }
+    }
+
+    #[test]
+    fn header_contents() {
+        let map = parse_dummy("two_ix.dg");
+        let header = read_header(&pack(&map, true).unwrap()).unwrap().unwrap();
+
+        assert_eq!(header.compiler_version, env!("CARGO_PKG_VERSION"));
+
+        let ids = header.toc.unwrap().into_iter().map(|v| v.id);
+        assert_eq!(ids.collect::<Vec<_>>(), ["First", "Second"]);
+    }
+
+    #[test]
+    fn load_one_interaction() {
+        let map = parse_dummy("rodrick.dg");
+
+        for toc in [false, true] {
+            let packed = pack(&map, toc).unwrap();
+
+            for (id, ix) in &map {
+                let loaded = deserialize_interaction(&packed, id).unwrap();
+                assert_eq!(loaded.as_ref(), Some(ix));
+            }
+
+            assert_eq!(deserialize_interaction(&packed, "Nope").unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn legacy_files_still_load() {
+        let map = parse_dummy("rodrick.dg");
+        let legacy = bincode::serialize(&map).unwrap();
+
+        assert_eq!(read_header(&legacy).unwrap(), None);
+        assert_eq!(deserialize(&legacy).unwrap(), map);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let mut packed = pack(&parse_dummy("rodrick.dg"), false).unwrap();
+        packed[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+
+        let err = deserialize(&packed).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Container(ContainerError::UnsupportedVersion { found, .. })
+                if found == FORMAT_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn rejects_corrupted_files() {
+        let mut packed = pack(&parse_dummy("rodrick.dg"), false).unwrap();
+        *packed.last_mut().unwrap() ^= 0xFF;
+
+        let err = deserialize(&packed).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Container(ContainerError::HashMismatch { .. })
+        ));
+
+        let err = deserialize(&packed[..5]).unwrap_err();
+        assert!(matches!(err, Error::Container(ContainerError::Truncated)));
+    }
+
+    #[test]
+    fn huge_length_prefix() {
+        // a compiler version string claiming to be 2^48 bytes long
+        let mut packed = MAGIC.to_vec();
+        packed.extend(FORMAT_VERSION.to_le_bytes());
+        packed.extend([0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0]);
+        packed.extend(b"abc");
+
+        let err = deserialize(&packed).unwrap_err();
+        assert!(matches!(err, Error::Container(ContainerError::Truncated)));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        for data in [&b"% Greeting\nNAME Mira\n"[..], b"", b"\x01"] {
+            let err = deserialize(data).unwrap_err();
+            assert!(matches!(err, Error::Container(ContainerError::NotDgc)));
+        }
+    }
+
+    /// Swap out the header of a packed file, with `edit` getting
+    /// a chance to mess with it first
+    fn repack(packed: &[u8], rehash: bool, edit: impl FnOnce(&mut Header)) -> Vec<u8> {
+        let (header, payload) = unpack(packed).unwrap();
+        let mut header = header.unwrap();
+        edit(&mut header);
+
+        if rehash {
+            header.hash = checksum(&header, payload).unwrap();
+        }
+
+        let mut res = packed[..6].to_vec();
+        bincode::serialize_into(&mut res, &header).unwrap();
+        res.extend(payload);
+        res
+    }
+
+    #[test]
+    fn hash_covers_header() {
+        let packed = pack(&parse_dummy("two_ix.dg"), true).unwrap();
+        let packed = repack(&packed, false, |h| h.toc.as_mut().unwrap()[0].len += 1);
+
+        let err = deserialize_interaction(&packed, "First").unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Container(ContainerError::HashMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_toc_entries() {
+        let packed = pack(&parse_dummy("two_ix.dg"), true).unwrap();
+        let packed = repack(&packed, true, |h| {
+            let toc = h.toc.as_mut().unwrap();
+            toc[0].offset = u64::MAX;
+            toc[1].len = u64::MAX / 2;
+        });
+
+        for id in ["First", "Second"] {
+            let err = deserialize_interaction(&packed, id).unwrap_err();
+            assert!(matches!(
+                err,
+                Error::Container(ContainerError::BadTocEntry(bad)) if bad == id
+            ));
+        }
+    }
+}
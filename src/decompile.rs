@@ -0,0 +1,197 @@
+//!
+//! Turning interactions back into `.dg` source.
+//!
+//! The output is canonical: interactions sorted by ID, metadata
+//! in a fixed order, one line per line of content. Parsing the
+//! output gives back the same `InteractionMap`, as long as the
+//! content doesn't rely on stuff the parser throws away, like
+//! whitespace at the start or end of a line.
+//!
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::consts::{PREFIX_CHOICE, PREFIX_GOTO_LABEL, SEPARATOR};
+use crate::{DialogueEnding, Interaction, InteractionMap, Label, Metaline, Page, Speaker};
+
+/// One metadata line, or nothing if there's no change
+fn write_metaline<T>(out: &mut String, key: &str, line: &Metaline<T>, fmt: impl Fn(&T) -> &str) {
+    let (val, pageonly) = match line {
+        Metaline::Permanent(val) => (val, false),
+        Metaline::PageOnly(val) => (val, true),
+        Metaline::NoChange => return,
+    };
+
+    if pageonly {
+        out.push_str("PageOnly ");
+    }
+
+    let _ = writeln!(out, "{} {}", key, fmt(val));
+}
+
+fn speaker_value(speaker: &Speaker) -> &str {
+    match speaker {
+        Speaker::Named(name) => name,
+        Speaker::Narrator => "_",
+        Speaker::Unknown => "?",
+    }
+}
+
+/// Message content, with newlines turned back into
+/// lines ending in a literal `\n`
+pub(crate) fn write_content(out: &mut String, content: &str) {
+    let mut lines = content.split('\n').peekable();
+
+    while let Some(line) = lines.next() {
+        if lines.peek().is_some() {
+            let _ = writeln!(out, "{}\\n", line);
+        } else if !line.is_empty() {
+            let _ = writeln!(out, "{}", line);
+        }
+    }
+}
+
+pub(crate) fn write_ending(out: &mut String, ending: &DialogueEnding) {
+    match ending {
+        DialogueEnding::End => {}
+
+        DialogueEnding::Label(Label::Goto(id)) => {
+            let _ = writeln!(out, "{} {}\n", PREFIX_GOTO_LABEL, id);
+        }
+
+        DialogueEnding::Choices(choices) => {
+            for choice in choices {
+                let _ = writeln!(out, "{} {}", PREFIX_CHOICE, choice.text);
+
+                if let Some(Label::Goto(ref id)) = choice.label {
+                    let _ = writeln!(out, "{} {}", PREFIX_GOTO_LABEL, id);
+                }
+
+                out.push('\n');
+            }
+        }
+    }
+}
+
+/// A page's metadata, content, and (if it's the last
+/// page) the interaction's ending, up to and including
+/// the `---` after it
+pub(crate) fn write_page(out: &mut String, page: &Page, ending: Option<&DialogueEnding>) {
+    write_metaline(out, "NAME", &page.metadata.speaker, speaker_value);
+    write_metaline(out, "VOX", &page.metadata.vox, String::as_str);
+    out.push('\n');
+
+    write_content(out, &page.content);
+    out.push('\n');
+
+    if let Some(ending) = ending {
+        write_ending(out, ending);
+    }
+
+    let _ = writeln!(out, "{}", SEPARATOR);
+}
+
+/// One interaction, starting with its `%` header
+pub fn decompile_interaction(id: &str, ix: &Interaction) -> String {
+    let mut res = format!("% {}\n", id);
+
+    let last = ix.pages.len().saturating_sub(1);
+    for (i, page) in ix.pages.iter().enumerate() {
+        let ending = (i == last).then_some(&ix.ending);
+        write_page(&mut res, page, ending);
+    }
+
+    res
+}
+
+/// Print every interaction in the map as `.dg` source,
+/// sorted by ID
+pub fn decompile(map: &InteractionMap) -> String {
+    let sorted = map.iter().collect::<BTreeMap<_, _>>();
+
+    sorted
+        .into_iter()
+        .map(|(id, ix)| decompile_interaction(id, ix))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::dummy_path;
+    use crate::DgParser;
+
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    fn parse(path: PathBuf, data: &str) -> InteractionMap {
+        DgParser::new(path).parse_all(data).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let names = [
+            "small_ix.dg",
+            "link.dg",
+            "link_between_ix.dg",
+            "unlink.dg",
+            "two_ix.dg",
+            "one_ix_many_pages.dg",
+            "rodrick.dg",
+            "pets/main.dg",
+            "import_sub.dg",
+            "newlines.dg",
+            "empties.dg",
+            "narrator.dg",
+            "pageonly.dg",
+            "vox_markers.dg",
+        ];
+
+        for name in names {
+            let path = dummy_path(name);
+            let map = parse(path.clone(), &std::fs::read_to_string(&path).unwrap());
+            let printed = decompile(&map);
+
+            assert_eq!(parse(path, &printed), map, "{} did not round-trip", name);
+        }
+    }
+
+    #[test]
+    fn canonical_output() {
+        let path = dummy_path("pageonly.dg");
+        let map = parse(path.clone(), &std::fs::read_to_string(&path).unwrap());
+
+        let expected = r#"% PageOnly Test
+NAME Mira
+VOX Mira
+
+What's up?
+
+---
+PageOnly VOX Ethan
+
+Nothing much...
+
+---
+
+Alright, why am I talking to myself?\n
+Who's making me do this?
+
+---
+"#;
+
+        assert_eq!(decompile(&map), expected);
+    }
+
+    #[test]
+    fn choices_and_gotos() {
+        let path = dummy_path("rodrick.dg");
+        let map = parse(path.clone(), &std::fs::read_to_string(&path).unwrap());
+        let printed = decompile_interaction("RodrickSign", &map["RodrickSign"]);
+
+        assert!(printed.ends_with(
+            "Are you smart?\n\n> Nope\n@ RodrickSign_Nope\n\n\
+            > Definitely not\n@ RodrickSign_DefNot\n\n---\n"
+        ));
+    }
+}
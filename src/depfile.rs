@@ -0,0 +1,155 @@
+//!
+//! Lists of every file a compile read, for build systems
+//! that only want to recompile when one of them changes
+//!
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::Error;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum DepfileFormat {
+    /// Makefile rule, like `gcc -MD -MP` writes
+    #[default]
+    Make,
+
+    /// `{ "output": ..., "inputs": [...] }`
+    Json,
+}
+
+impl DepfileFormat {
+    /// Where the depfile for `output` goes
+    pub fn path_for(self, output: &Path) -> PathBuf {
+        let mut res = output.as_os_str().to_owned();
+
+        res.push(match self {
+            Self::Make => ".d",
+            Self::Json => ".deps.json",
+        });
+
+        res.into()
+    }
+}
+
+#[derive(Serialize)]
+struct Manifest<'a> {
+    output: &'a Path,
+    inputs: &'a [PathBuf],
+}
+
+/// Spaces, `#` and `$` mean something else in Make
+fn escape_make(path: &Path) -> String {
+    path.to_string_lossy()
+        .replace('$', "$$")
+        .replace(' ', "\\ ")
+        .replace('#', "\\#")
+}
+
+/// A depfile saying `output` was built from `inputs`
+pub fn render_depfile(
+    output: &Path,
+    inputs: &[PathBuf],
+    format: DepfileFormat,
+) -> Result<String, Error> {
+    match format {
+        DepfileFormat::Make => {
+            let mut res = escape_make(output) + ":";
+            for input in inputs {
+                res.push(' ');
+                res.push_str(&escape_make(input));
+            }
+
+            res.push('\n');
+
+            // empty rules for everything, so deleting a file
+            // doesn't make `make` give up on the whole build
+            for input in inputs {
+                res.push_str(&format!("\n{}:\n", escape_make(input)));
+            }
+
+            Ok(res)
+        }
+
+        DepfileFormat::Json => {
+            let manifest = Manifest { output, inputs };
+            serde_json::to_string_pretty(&manifest)
+                .map(|v| v + "\n")
+                .map_err(|e| Error::Serialize(e.into()))
+        }
+    }
+}
+
+/// Write the depfile next to `output`, and give back its path
+pub fn write_depfile(
+    output: &Path,
+    inputs: &[PathBuf],
+    format: DepfileFormat,
+) -> Result<PathBuf, Error> {
+    let path = format.path_for(output);
+    let data = render_depfile(output, inputs, format)?;
+
+    fs::write(&path, data).map_err(|e| Error::Write(path.clone(), e))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    fn inputs() -> Vec<PathBuf> {
+        vec!["main.dg".into(), "shops/general store.dg".into()]
+    }
+
+    #[test]
+    fn make_depfile() {
+        let res = render_depfile(Path::new("out.dgc"), &inputs(), DepfileFormat::Make).unwrap();
+        let expected = "out.dgc: main.dg shops/general\\ store.dg\n\
+            \n\
+            main.dg:\n\
+            \n\
+            shops/general\\ store.dg:\n";
+
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn make_escapes() {
+        let path = Path::new("$HOME/#1 $(shop).dg");
+        assert_eq!(escape_make(path), "$$HOME/\\#1\\ $$(shop).dg");
+    }
+
+    #[test]
+    fn json_depfile() {
+        let res = render_depfile(Path::new("out.dgc"), &inputs(), DepfileFormat::Json).unwrap();
+        let expected = r#"{
+  "output": "out.dgc",
+  "inputs": [
+    "main.dg",
+    "shops/general store.dg"
+  ]
+}
+"#;
+
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn depfile_paths() {
+        let out = Path::new("build/out.dgc");
+
+        assert_eq!(
+            DepfileFormat::Make.path_for(out),
+            Path::new("build/out.dgc.d")
+        );
+        assert_eq!(
+            DepfileFormat::Json.path_for(out),
+            Path::new("build/out.dgc.deps.json")
+        );
+    }
+}
@@ -0,0 +1,299 @@
+//!
+//! Compiler-style rendering of parse errors, with the
+//! offending line of source and a caret under it
+//!
+
+use std::fmt::Write;
+
+use crate::comptime::{ScriptError, ScriptErrorKind};
+use crate::pages::{ParseError, ParseErrorKind, Span};
+use crate::SourceProvider;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// The innermost error in a chain of imports, which is
+/// the one the writer actually has to go fix
+struct Leaf<'a> {
+    span: &'a Span,
+    message: String,
+    hint: Option<&'static str>,
+}
+
+/// One `Import` or `Execute` line that led to the error
+struct Via<'a> {
+    span: &'a Span,
+    directive: &'static str,
+}
+
+fn parse_hint(kind: &ParseErrorKind) -> Option<&'static str> {
+    use ParseErrorKind::*;
+
+    Some(match kind {
+        PageAfterEnding => {
+            "choices and `@` gotos must go on the last page of an interaction. \
+            Start a new interaction with `% ID` for anything said after them."
+        }
+
+        MixedEndings(_) => {
+            "an interaction can end with a list of `>` choices or a single `@` goto, \
+            but not both. Each choice can only have one `@` under it."
+        }
+
+        MalformedEnding(_) => {
+            "after a message, only `>` choices and `@` gotos are allowed \
+            until the next `---`"
+        }
+
+        PushDuplicateIX(..) => {
+            "interaction IDs must be unique, including ones from imported files. \
+            `Import file.dg as Name` puts a file's IDs under `Name::`"
+        }
+
+        NotMeta(_) => {
+            "metadata looks like `NAME value`. \
+            Leave an empty line before the message."
+        }
+
+        InvalidMeta(_) => {
+            "valid directives are `NAME`, `VOX`, and either of those after `PageOnly`"
+        }
+
+        PushPageNoIX | EndingNoIX => "start an interaction with `% ID` before writing pages",
+
+        PushEmptyIX => "an interaction needs at least one page, or an `Import` inside it",
+
+        _ => return None,
+    })
+}
+
+fn script_hint(kind: &ScriptErrorKind) -> Option<&'static str> {
+    use ScriptErrorKind::*;
+
+    Some(match kind {
+        NoSuchCommand => "valid commands are Echo, Link, Unlink, Import, Execute, and Quit",
+        InvalidLink => "a link looks like `Link NAME value`, followed by one pair per line",
+        DoubleLink => "`Unlink` the existing link before linking the same target again",
+        FileOpen(_) => "paths are relative to the directory of the file the directive is in",
+        NotFound(..) => "add the folder it's in with `-I`, or start the path with `@/` to look in the project root",
+        DuplicateIX(..) => "`Import file.dg as Name` puts a file's IDs under `Name::`",
+        InvalidImport => "a selective import looks like `Import Greeting, Farewell from file.dg`",
+        NoSuchInteraction(..) => "IDs are case-sensitive, and can't have the namespace in front",
+        InvalidNamespace(_) => "a namespace is a single word, like `Import shop.dg as Shop`",
+        ImportCycle(_) => "move whatever the files need from each other into a third file",
+
+        _ => return None,
+    })
+}
+
+/// Follow `Panic`, `Import` and `Execute` errors down
+/// to the one that started it all
+fn unwind(err: &ParseError) -> (Vec<Via<'_>>, Leaf<'_>) {
+    let mut vias = vec![];
+    let mut err = err;
+
+    loop {
+        let ParseErrorKind::Panic(ref script) = err.kind else {
+            let leaf = Leaf {
+                span: &err.span,
+                message: err.kind.to_string(),
+                hint: parse_hint(&err.kind),
+            };
+
+            return (vias, leaf);
+        };
+
+        let mut script: &ScriptError = script;
+        loop {
+            match script.kind {
+                ScriptErrorKind::Import(_, ref inner) => {
+                    vias.push(Via {
+                        span: &script.span,
+                        directive: "imported",
+                    });
+
+                    err = inner;
+                    break;
+                }
+
+                ScriptErrorKind::Execute(_, ref inner) => {
+                    vias.push(Via {
+                        span: &script.span,
+                        directive: "executed",
+                    });
+
+                    script = inner;
+                }
+
+                ref kind => {
+                    let leaf = Leaf {
+                        span: &script.span,
+                        message: kind.to_string(),
+                        hint: script_hint(kind),
+                    };
+
+                    return (vias, leaf);
+                }
+            }
+        }
+    }
+}
+
+/// Render a parse error the way a compiler would, with the
+/// source snippet, the chain of `Import`s that led to it, and
+/// a hint for common mistakes.
+///
+/// The snippet is read through `sources`, which should be the
+/// same provider the file was parsed with. Pass `color = false`
+/// for plain text, ex. when not writing to a terminal.
+pub fn render_diagnostic(err: &ParseError, sources: &dyn SourceProvider, color: bool) -> String {
+    let paint = |style: &str, text: &str| {
+        if color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_owned()
+        }
+    };
+
+    let (vias, leaf) = unwind(err);
+    let span = leaf.span;
+
+    // the line might not exist if the source can't be read
+    // again, like when reading from stdin... just skip the snippet
+    let source_line = sources.read(&span.path).ok().and_then(|src| {
+        src.lines()
+            .nth(span.line.wrapping_sub(1))
+            .map(str::to_owned)
+    });
+
+    let gutter = span.line.to_string().len();
+    let pad = " ".repeat(gutter);
+    let bar = paint(BLUE, "|");
+
+    let mut res = String::new();
+    let _ = writeln!(
+        res,
+        "{}: {}",
+        paint(RED, "error"),
+        paint(BOLD, &leaf.message)
+    );
+    let _ = writeln!(res, "{}{} {}", pad, paint(BLUE, "-->"), span);
+
+    if let Some(line) = source_line {
+        let col = span.col.max(1) - 1;
+        let indent = line.chars().take(col).collect::<String>();
+        let width = line.chars().skip(col).count().max(1);
+
+        // keep tabs in the indent so the caret lines up
+        let indent = indent
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+
+        let lineno = paint(BLUE, &span.line.to_string());
+        let carets = paint(RED, &"^".repeat(width));
+
+        let _ = writeln!(res, "{} {}", pad, bar);
+        let _ = writeln!(res, "{} {} {}", lineno, bar, line);
+        let _ = writeln!(res, "{} {} {}{}", pad, bar, indent, carets);
+    }
+
+    if !vias.is_empty() || leaf.hint.is_some() {
+        let _ = writeln!(res, "{} {}", pad, bar);
+    }
+
+    // innermost first, so it reads like a backtrace
+    for via in vias.iter().rev() {
+        let note = format!("{} from {}", via.directive, via.span);
+        let _ = writeln!(
+            res,
+            "{} {} {}: {}",
+            pad,
+            paint(BLUE, "="),
+            paint(BOLD, "note"),
+            note
+        );
+    }
+
+    if let Some(hint) = leaf.hint {
+        let _ = writeln!(
+            res,
+            "{} {} {}: {}",
+            pad,
+            paint(BLUE, "="),
+            paint(CYAN, "hint"),
+            hint
+        );
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::dummy_path;
+    use crate::{DgParser, FsSource, MemorySource};
+
+    use pretty_assertions::assert_eq;
+
+    fn parse_err(name: &str) -> ParseError {
+        let path = dummy_path(name);
+        let data = std::fs::read_to_string(&path).unwrap();
+        DgParser::new(path).parse_all(&data).unwrap_err()
+    }
+
+    #[test]
+    fn snippet_and_caret() {
+        let err = parse_err("bad_meta.dg");
+        let rendered = render_diagnostic(&err, &FsSource, false);
+        let lines = rendered.lines().collect::<Vec<_>>();
+
+        assert_eq!(
+            lines[0],
+            "error: COLOR Red is not a valid metadata directive"
+        );
+        assert!(lines[1].ends_with("bad_meta.dg:3:5"));
+        assert_eq!(lines[3], "3 |     COLOR Red");
+        assert_eq!(lines[4], "  |     ^^^^^^^^^");
+        assert!(lines[6].starts_with("  = hint: valid directives"));
+    }
+
+    #[test]
+    fn import_chain() {
+        let err = parse_err("import_bad.dg");
+        let rendered = render_diagnostic(&err, &FsSource, false);
+
+        assert!(rendered.contains("bad_meta.dg:3:5"));
+        assert!(rendered.contains("= note: imported from "));
+        assert!(rendered.contains("import_bad.dg:9:1"));
+    }
+
+    #[test]
+    fn plain_has_no_escapes() {
+        let err = parse_err("vsauce.dg");
+        assert!(!render_diagnostic(&err, &FsSource, false).contains('\x1b'));
+        assert!(render_diagnostic(&err, &FsSource, true).contains('\x1b'));
+    }
+
+    #[test]
+    fn snippet_from_memory() {
+        let data = "% Greeting\nNAME Mira\nCOLOR Red\n\nHi\n\n---\n";
+        let src = MemorySource::new().with("main.dg", data);
+
+        let err = DgParser::new("main.dg".into())
+            .sources(src.clone())
+            .parse_all(data)
+            .unwrap_err();
+
+        let rendered = render_diagnostic(&err, &src, false);
+        assert!(rendered.contains("3 | COLOR Red"));
+
+        // nothing on disk at that path, so no snippet
+        let rendered = render_diagnostic(&err, &FsSource, false);
+        assert!(!rendered.contains("3 | "));
+    }
+}
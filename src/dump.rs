@@ -0,0 +1,138 @@
+//!
+//! Human-readable dumps of compiled interactions, for
+//! diffing build artifacts without writing any Rust
+//!
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::{DialogueEnding, Error, Interaction, InteractionMap, Label};
+
+/// Interactions sorted by ID, so dumps are stable between
+/// builds even though `InteractionMap` is a `HashMap`
+pub(crate) fn sorted(map: &InteractionMap) -> BTreeMap<&str, &Interaction> {
+    map.iter().map(|(k, v)| (k.as_str(), v)).collect()
+}
+
+/// Pretty-print every interaction, page, and ending.
+///
+/// Metadata is printed exactly as it was compiled, so
+/// `NoChange` and `PageOnly` lines show up as-is.
+pub fn dump(map: &InteractionMap) -> String {
+    let mut res = String::new();
+
+    for (i, (id, ix)) in sorted(map).into_iter().enumerate() {
+        if i > 0 {
+            res.push('\n');
+        }
+
+        let _ = writeln!(res, "== {} ==", id);
+
+        for (n, page) in ix.pages.iter().enumerate() {
+            let _ = writeln!(res, "[page {}]", n + 1);
+            let _ = writeln!(res, "speaker: {:?}", page.metadata.speaker);
+            let _ = writeln!(res, "vox: {:?}", page.metadata.vox);
+            let _ = writeln!(res, "content: {:?}", page.content);
+        }
+
+        match ix.ending {
+            DialogueEnding::End => res.push_str("ending: End\n"),
+
+            DialogueEnding::Label(Label::Goto(ref id)) => {
+                let _ = writeln!(res, "ending: Goto {}", id);
+            }
+
+            DialogueEnding::Choices(ref choices) => {
+                res.push_str("ending: Choices\n");
+
+                for choice in choices {
+                    let _ = write!(res, "  > {:?}", choice.text);
+
+                    match choice.label {
+                        Some(Label::Goto(ref id)) => {
+                            let _ = writeln!(res, " -> {}", id);
+                        }
+
+                        None => res.push('\n'),
+                    }
+                }
+            }
+        }
+    }
+
+    res
+}
+
+/// Same as `dump`, but as pretty-printed JSON with
+/// the interactions sorted by ID
+pub fn dump_json(map: &InteractionMap) -> Result<String, Error> {
+    serde_json::to_string_pretty(&sorted(map)).map_err(|e| Error::Serialize(e.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::parse_dummy;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn dump_rodrick() {
+        let dumped = dump(&parse_dummy("rodrick.dg"));
+        let expected = r#"== RodrickSign ==
+[page 1]
+speaker: Permanent(Named("Rodrick Sign Co."))
+vox: Permanent("Default")
+content: "So... you're reading a sign, eh?"
+[page 2]
+speaker: NoChange
+vox: NoChange
+content: "Well..."
+[page 3]
+speaker: NoChange
+vox: NoChange
+content: "Are you smart?"
+ending: Choices
+  > "Nope" -> RodrickSign_Nope
+  > "Definitely not" -> RodrickSign_DefNot
+
+== RodrickSign_DefNot ==
+[page 1]
+speaker: Permanent(Named("Rodrick Sign Co."))
+vox: Permanent("Default")
+content: "Yeah, I definitely didn't think so."
+ending: Goto RodrickSign_Exit
+
+== RodrickSign_Exit ==
+[page 1]
+speaker: Permanent(Named("Rodrick Sign Co."))
+vox: Permanent("Default")
+content: "Come back when you're smart."
+ending: End
+
+== RodrickSign_Nope ==
+[page 1]
+speaker: Permanent(Named("Rodrick Sign Co."))
+vox: Permanent("Default")
+content: "Yeah, I didn't think so."
+ending: Goto RodrickSign_Exit
+"#;
+
+        assert_eq!(dumped, expected);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let map = parse_dummy("import_sub.dg");
+        let json = dump_json(&map).unwrap();
+
+        // same input, same output, despite the HashMap,
+        // even with the entries inserted in reverse
+        let mut entries = map.clone().into_iter().collect::<Vec<_>>();
+        entries.sort_by(|a, b| b.0.cmp(&a.0));
+        let reversed = entries.into_iter().collect::<InteractionMap>();
+
+        assert_eq!(json, dump_json(&reversed).unwrap());
+        assert_eq!(serde_json::from_str::<InteractionMap>(&json).unwrap(), map);
+    }
+}
@@ -0,0 +1,68 @@
+//!
+//! The error type for everything the library
+//! can do, from reading files to serializing
+//!
+
+use std::io;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::{
+    ContainerError, DanglingGotos, ParseErrors, RunnerError, TranscriptError, Unformatted,
+};
+
+/// Errors from whichever serialization library was used
+type BoxedError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Anything that can go wrong in `compile` and friends.
+///
+/// Errors from comptime scripts show up inside `Parse`,
+/// along with the line of the script they came from.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Could not read {0}")]
+    Read(PathBuf, #[source] io::Error),
+
+    #[error("Could not write {0}")]
+    Write(PathBuf, #[source] io::Error),
+
+    /// Errors reading stdin or writing stdout, which
+    /// don't have a path to blame
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Parse(#[from] ParseErrors),
+
+    #[error(transparent)]
+    DanglingGotos(#[from] DanglingGotos),
+
+    #[error(transparent)]
+    Unformatted(#[from] Unformatted),
+
+    /// The formatter's output didn't compile to the same
+    /// interactions as the original, so it was thrown away
+    #[error("Formatting {0} would change what it compiles to")]
+    FormatChanged(PathBuf),
+
+    #[error(transparent)]
+    Runner(#[from] RunnerError),
+
+    #[error(transparent)]
+    Transcript(#[from] TranscriptError),
+
+    #[error("{0} transcript test(s) failed")]
+    TestsFailed(usize),
+
+    #[error("Could not serialize interactions")]
+    Serialize(#[source] BoxedError),
+
+    /// The `.dgc` header is from a newer version,
+    /// or doesn't match what's in the file
+    #[error(transparent)]
+    Container(#[from] ContainerError),
+
+    #[error("Could not deserialize interactions")]
+    Deserialize(#[source] BoxedError),
+}
@@ -0,0 +1,354 @@
+//!
+//! The `.dg` formatter.
+//!
+//! Follows the same states as the parser, one line at a time,
+//! but spits out lines in a canonical layout instead of building
+//! interactions. Comptime blocks are kept as-is besides spacing,
+//! so `//` comments and directives like `Link` survive.
+//!
+
+use std::fs;
+use std::mem;
+use std::path::PathBuf;
+
+use crate::consts::{COMPTIME_BORDER, PREFIX_CHOICE, SEPARATOR};
+use crate::{DgParser, Error, ParseErrors};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum FmtState {
+    #[default]
+    Metadata,
+    Message,
+    Choices,
+    Comptime,
+}
+
+#[derive(Default)]
+struct Formatter {
+    state: FmtState,
+    out: Vec<String>,
+
+    /// Lines of the message or comptime block being formatted
+    buf: Vec<String>,
+
+    /// Whether any choices/gotos were written since the message
+    had_ending: bool,
+}
+
+impl Formatter {
+    fn emit(&mut self, line: impl Into<String>) {
+        self.out.push(line.into());
+    }
+
+    /// `NAME    Mira` becomes `NAME Mira`, and so on.
+    /// Only the whitespace the parser skips over is touched.
+    fn format_metaline(line: &str) -> String {
+        let mut words = vec![];
+        let mut rest = line;
+
+        // same splitting as the parser, up to twice for `PageOnly`
+        while let Some((k, v)) = rest.split_once(char::is_whitespace) {
+            words.push(k);
+            rest = v.trim_start();
+
+            if k != "PageOnly" {
+                break;
+            }
+        }
+
+        words.push(rest);
+        words.join(" ")
+    }
+
+    /// Message lines, re-wrapped so there's one line per
+    /// line of text the player actually sees
+    fn flush_message(&mut self) {
+        let mut joined = vec![];
+
+        for line in mem::take(&mut self.buf) {
+            joined.push(line);
+
+            if joined.last().is_some_and(|v| v.ends_with("\\n")) {
+                self.out.push(mem::take(&mut joined).join(" "));
+            }
+        }
+
+        if !joined.is_empty() {
+            self.out.push(joined.join(" "));
+        }
+    }
+
+    /// Comptime block contents, with blank lines collapsed and
+    /// trimmed from the ends. Empty lines between directives
+    /// matter (they end a `Link`), but more than one in a row
+    /// doesn't, and the script always ends with one anyway.
+    fn flush_comptime(&mut self) {
+        let mut body: Vec<String> = vec![];
+
+        for line in mem::take(&mut self.buf) {
+            let line = line.trim();
+
+            if line.is_empty() && body.last().is_none_or(|v| v.is_empty()) {
+                continue;
+            }
+
+            body.push(line.to_owned());
+        }
+
+        while body.last().is_some_and(|v| v.is_empty()) {
+            body.pop();
+        }
+
+        self.emit("");
+        if !body.is_empty() {
+            self.out.extend(body);
+            self.emit("");
+        }
+    }
+
+    fn line(&mut self, raw: &str) {
+        let line = raw.trim();
+
+        match self.state {
+            FmtState::Comptime => {
+                let last_is_border = self.buf.last().is_some_and(|v| v.trim() == COMPTIME_BORDER);
+
+                if line == SEPARATOR && last_is_border {
+                    self.buf.pop();
+                    self.flush_comptime();
+                    self.emit(COMPTIME_BORDER);
+                    self.emit(SEPARATOR);
+                    self.state = FmtState::Metadata;
+                } else {
+                    self.buf.push(raw.to_owned());
+                }
+            }
+
+            FmtState::Metadata if line.is_empty() => {
+                self.emit("");
+                self.state = FmtState::Message;
+            }
+
+            FmtState::Metadata if line == COMPTIME_BORDER => {
+                self.emit(COMPTIME_BORDER);
+                self.state = FmtState::Comptime;
+            }
+
+            FmtState::Metadata => self.emit(Self::format_metaline(line)),
+
+            FmtState::Message if line.is_empty() => {
+                self.flush_message();
+                self.emit("");
+                self.state = FmtState::Choices;
+            }
+
+            FmtState::Message => self.buf.push(line.to_owned()),
+
+            FmtState::Choices if line.is_empty() => {}
+
+            FmtState::Choices if line == SEPARATOR => {
+                if mem::take(&mut self.had_ending) {
+                    self.emit("");
+                }
+
+                self.emit(SEPARATOR);
+                self.state = FmtState::Metadata;
+            }
+
+            FmtState::Choices => {
+                // one empty line between each choice
+                if line.starts_with(PREFIX_CHOICE) && self.had_ending {
+                    self.emit("");
+                }
+
+                self.had_ending = true;
+                self.emit(line);
+            }
+        }
+    }
+
+    fn finish(mut self) -> String {
+        // whatever's left was never closed off, but it's
+        // still the user's text, so don't just delete it
+        match self.state {
+            FmtState::Message => self.flush_message(),
+            FmtState::Comptime => self.out.append(&mut self.buf),
+            _ => {}
+        }
+
+        while self.out.last().is_some_and(|v| v.is_empty()) {
+            self.out.pop();
+        }
+
+        let mut res = self.out.join("\n");
+        res.push('\n');
+        res
+    }
+}
+
+/// Rewrite `.dg` source into the canonical layout.
+///
+/// `parser` is set up for wherever the source lives, so its
+/// imports can be found. Both versions get parsed to make sure
+/// they compile to the exact same thing, and if they somehow
+/// don't, this fails with `Error::FormatChanged` instead of
+/// breaking the file.
+pub fn format_source(data: &str, parser: DgParser) -> Result<String, Error> {
+    let mut fmt = Formatter::default();
+    for line in data.lines() {
+        fmt.line(line);
+    }
+
+    let res = fmt.finish();
+
+    let parse = |mut parser: DgParser, data: &str| {
+        let (map, errors) = parser.parse_all_recovering(data);
+        match errors.is_empty() {
+            true => Ok(map),
+            false => Err(ParseErrors(errors)),
+        }
+    };
+
+    let path = parser.path().to_owned();
+    if parse(parser.fresh(), data)? != parse(parser, &res).unwrap_or_default() {
+        return Err(Error::FormatChanged(path));
+    }
+
+    Ok(res)
+}
+
+/// Format the file `parser` is for in place.
+///
+/// With `check`, the file is left alone. Either way, returns
+/// whether the file was (or would have been) changed.
+pub fn format_file(parser: DgParser, check: bool) -> Result<bool, Error> {
+    let path = parser.path().to_owned();
+    let data = parser
+        .read_entry()
+        .map_err(|e| Error::Read(path.clone(), e))?;
+    let res = format_source(&data, parser)?;
+
+    let changed = res != data;
+    if changed && !check {
+        fs::write(&path, res).map_err(|e| Error::Write(path, e))?;
+    }
+
+    Ok(changed)
+}
+
+/// Files that `dg fmt --check` found weren't formatted
+#[derive(Debug, thiserror::Error, PartialEq)]
+#[error("{} file(s) need formatting", .0.len())]
+pub struct Unformatted(pub Vec<PathBuf>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::dummy_path;
+    use crate::InteractionMap;
+
+    use pretty_assertions::assert_eq;
+
+    fn format_dummy(name: &str) -> String {
+        let path = dummy_path(name);
+        format_source(&fs::read_to_string(&path).unwrap(), DgParser::new(path)).unwrap()
+    }
+
+    #[test]
+    fn already_formatted() {
+        for name in [
+            "rodrick.dg",
+            "link.dg",
+            "unlink.dg",
+            "two_ix.dg",
+            "pageonly.dg",
+        ] {
+            let path = dummy_path(name);
+            let data = fs::read_to_string(&path).unwrap();
+
+            // the dummy files all end with an extra empty line
+            let expected = format!("{}\n", data.trim_end());
+            assert_eq!(
+                format_source(&data, DgParser::new(path)).unwrap(),
+                expected,
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn idempotent() {
+        for name in ["messy.dg", "newlines.dg", "empties.dg", "import.dg"] {
+            let once = format_dummy(name);
+            let twice = format_source(&once, DgParser::new(dummy_path(name))).unwrap();
+
+            assert_eq!(once, twice, "{}", name);
+        }
+    }
+
+    #[test]
+    fn messy() {
+        let expected = r#"% Messy
+NAME Siva
+PageOnly VOX Terra
+
+This message was wrapped for no reason, and it goes on for a while.\n
+New line!
+
+---
+###
+
+// comments stay put
+Link NAME Mira
+VOX Mira
+
+###
+---
+NAME Mira
+
+Bye.
+
+> Choice one
+@ Messy
+
+> Choice two
+
+---
+"#;
+
+        assert_eq!(format_dummy("messy.dg"), expected);
+    }
+
+    #[test]
+    fn same_interactions() {
+        let path = dummy_path("messy.dg");
+        let parse =
+            |data: &str| -> InteractionMap { DgParser::new(path.clone()).parse_all(data).unwrap() };
+
+        let data = fs::read_to_string(&path).unwrap();
+        assert_eq!(parse(&data), parse(&format_dummy("messy.dg")));
+    }
+
+    #[test]
+    fn refuses_broken_files() {
+        let path = dummy_path("many_errors.dg");
+        let data = fs::read_to_string(&path).unwrap();
+
+        assert!(matches!(
+            format_source(&data, DgParser::new(path)),
+            Err(Error::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn uses_include_dirs() {
+        let path = dummy_path("chapters/include_dir.dg");
+        let data = fs::read_to_string(&path).unwrap();
+
+        assert!(format_source(&data, DgParser::new(path.clone())).is_err());
+
+        let parser = DgParser::new(path).include_dirs([dummy_path("shared")]);
+        assert_eq!(format_source(&data, parser).unwrap(), data);
+    }
+}
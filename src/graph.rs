@@ -0,0 +1,222 @@
+//!
+//! Exporting the interaction graph for narrative
+//! designers to look at, in Graphviz DOT or Mermaid
+//!
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use clap::ValueEnum;
+
+use crate::{DialogueEnding, Interaction, InteractionMap, Label};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    #[default]
+    Dot,
+
+    /// Mermaid flowchart
+    Mermaid,
+}
+
+/// One arrow in the graph
+struct Edge<'a> {
+    from: &'a str,
+    to: &'a str,
+
+    /// Choice text, or `None` for a plain goto
+    text: Option<&'a str>,
+}
+
+/// Interactions sorted by ID, plus every edge between them
+fn collect(map: &InteractionMap) -> (BTreeMap<&str, &Interaction>, Vec<Edge<'_>>) {
+    let sorted = map
+        .iter()
+        .map(|(k, v)| (k.as_str(), v))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut edges = vec![];
+    for (&from, ix) in &sorted {
+        for (choice, Label::Goto(to)) in ix.ending.labels() {
+            let text = match (&ix.ending, choice) {
+                (DialogueEnding::Choices(choices), Some(i)) => Some(choices[i].text.as_str()),
+                _ => None,
+            };
+
+            edges.push(Edge { from, to, text });
+        }
+    }
+
+    (sorted, edges)
+}
+
+/// ID, plus the speaker of the first page if it sets one
+fn node_label(id: &str, ix: &Interaction) -> Vec<String> {
+    let speaker = ix
+        .pages
+        .first()
+        .and_then(|page| page.metadata.speaker.try_unwrap())
+        .map(ToString::to_string)
+        .filter(|v| !v.is_empty());
+
+    std::iter::once(id.to_owned()).chain(speaker).collect()
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn mermaid_escape(text: &str) -> String {
+    text.replace('"', "#quot;")
+}
+
+/// Render the graph as Graphviz DOT
+pub fn to_dot(map: &InteractionMap) -> String {
+    let (nodes, edges) = collect(map);
+    let mut res = String::from("digraph {\n");
+
+    for (id, ix) in &nodes {
+        let label = node_label(id, ix)
+            .iter()
+            .map(|v| dot_escape(v))
+            .collect::<Vec<_>>()
+            .join("\\n");
+
+        let _ = writeln!(res, "    \"{}\" [label=\"{}\"];", dot_escape(id), label);
+    }
+
+    // gotos to interactions that don't exist
+    let missing = edges
+        .iter()
+        .map(|e| e.to)
+        .filter(|to| !map.contains_key(*to))
+        .collect::<BTreeSet<_>>();
+
+    for id in missing {
+        let _ = writeln!(res, "    \"{}\" [style=dashed];", dot_escape(id));
+    }
+
+    for edge in &edges {
+        let _ = write!(
+            res,
+            "    \"{}\" -> \"{}\"",
+            dot_escape(edge.from),
+            dot_escape(edge.to)
+        );
+
+        match edge.text {
+            Some(text) => {
+                let _ = writeln!(res, " [label=\"{}\"];", dot_escape(text));
+            }
+
+            None => res.push_str(";\n"),
+        }
+    }
+
+    res.push_str("}\n");
+    res
+}
+
+/// Render the graph as a Mermaid flowchart
+pub fn to_mermaid(map: &InteractionMap) -> String {
+    let (nodes, edges) = collect(map);
+    let mut res = String::from("flowchart TD\n");
+
+    // interaction IDs can have spaces and symbols in them,
+    // so every node gets a simple generated ID instead
+    let mut ids = nodes
+        .keys()
+        .enumerate()
+        .map(|(i, id)| (*id, format!("n{}", i)))
+        .collect::<BTreeMap<_, _>>();
+
+    for (id, ix) in &nodes {
+        let label = node_label(id, ix)
+            .iter()
+            .map(|v| mermaid_escape(v))
+            .collect::<Vec<_>>()
+            .join("<br/>");
+
+        let _ = writeln!(res, "    {}[\"{}\"]", ids[id], label);
+    }
+
+    for edge in edges.iter().filter(|e| !map.contains_key(e.to)) {
+        if ids.contains_key(edge.to) {
+            continue;
+        }
+
+        let node = format!("n{}", ids.len());
+        let _ = writeln!(
+            res,
+            "    {}[\"{}\"]:::missing",
+            node,
+            mermaid_escape(edge.to)
+        );
+        ids.insert(edge.to, node);
+    }
+
+    for edge in &edges {
+        let (from, to) = (&ids[edge.from], &ids[edge.to]);
+
+        let _ = match edge.text {
+            Some(text) => writeln!(res, "    {} -->|\"{}\"| {}", from, mermaid_escape(text), to),
+            None => writeln!(res, "    {} --> {}", from, to),
+        };
+    }
+
+    if ids.len() > nodes.len() {
+        res.push_str("    classDef missing stroke-dasharray: 5 5\n");
+    }
+
+    res
+}
+
+/// Render the graph in whichever format
+pub fn render_graph(map: &InteractionMap, format: GraphFormat) -> String {
+    match format {
+        GraphFormat::Dot => to_dot(map),
+        GraphFormat::Mermaid => to_mermaid(map),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::parse_dummy;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn rodrick_dot() {
+        let expected = r#"digraph {
+    "RodrickSign" [label="RodrickSign\nRodrick Sign Co."];
+    "RodrickSign_DefNot" [label="RodrickSign_DefNot\nRodrick Sign Co."];
+    "RodrickSign_Exit" [label="RodrickSign_Exit\nRodrick Sign Co."];
+    "RodrickSign_Nope" [label="RodrickSign_Nope\nRodrick Sign Co."];
+    "RodrickSign" -> "RodrickSign_Nope" [label="Nope"];
+    "RodrickSign" -> "RodrickSign_DefNot" [label="Definitely not"];
+    "RodrickSign_DefNot" -> "RodrickSign_Exit";
+    "RodrickSign_Nope" -> "RodrickSign_Exit";
+}
+"#;
+
+        assert_eq!(to_dot(&parse_dummy("rodrick.dg")), expected);
+    }
+
+    #[test]
+    fn dangling_mermaid() {
+        let expected = r#"flowchart TD
+    n0["Shop<br/>Shopkeeper"]
+    n1["Shop_Buy<br/>Shopkeeper"]
+    n2["Shop_Sell"]:::missing
+    n3["Shop_Exit"]:::missing
+    n0 -->|"Buying"| n1
+    n0 -->|"Selling"| n2
+    n1 --> n3
+    classDef missing stroke-dasharray: 5 5
+"#;
+
+        assert_eq!(to_mermaid(&parse_dummy("dangling.dg")), expected);
+    }
+}
@@ -6,26 +6,65 @@
 //!  \- &Cherry, 11/20/2023
 //!
 
-#![feature(if_let_guard)]
+use clap::ValueEnum;
 
-use clap::Parser;
-
-use std::fs::File;
-use std::io::{self, Read, Write};
+use std::fs;
 use std::path::{Path, PathBuf};
-use std::sync::OnceLock;
 
+mod check;
+mod cli;
 mod comptime;
 mod consts;
+mod container;
+mod decompile;
+mod depfile;
+mod diagnostic;
+mod dump;
+mod error;
+mod format;
+mod graph;
 mod pages;
 mod parser;
+mod play;
+mod reachability;
+mod runner;
+mod source;
+#[cfg(test)]
+mod test_util;
+mod transcript;
 
 use comptime::{Link, LinkKVPair};
 use parser::Result as ParseResult;
 
 // Re-exports
-pub use pages::{Interaction, InteractionMap, Metaline, Page, PageMeta, Speaker};
-pub use parser::{DgParser, DialogueChoice, DialogueEnding, Label};
+pub use check::{check_gotos, DanglingGoto, DanglingGotos};
+pub use cli::{
+    cli_main, print_error, Args, CheckArgs, Command, CompileArgs, DecompileArgs, DumpArgs, FmtArgs,
+    GraphArgs, ImportArgs, OutputArgs, PlayArgs,
+};
+pub use comptime::{ScriptError, ScriptErrorKind};
+pub use container::{
+    deserialize_interaction, pack, read_header, unpack, ContainerError, Header, TocEntry,
+    FORMAT_VERSION,
+};
+pub use decompile::{decompile, decompile_interaction};
+pub use depfile::{render_depfile, write_depfile, DepfileFormat};
+pub use diagnostic::render_diagnostic;
+pub use dump::{dump, dump_json};
+pub use error::Error;
+pub use format::{format_file, format_source, Unformatted};
+pub use graph::{render_graph, to_dot, to_mermaid, GraphFormat};
+pub use pages::{
+    Interaction, InteractionMap, Metaline, Page, PageMeta, ResolvedPage, Span, Speaker,
+};
+pub use parser::{
+    DgParser, DialogueChoice, DialogueEnding, Label, ParseError, ParseErrorKind, ParseErrors,
+};
+pub use play::play;
+pub use reachability::{analyze_reachability, Reachability};
+pub use runner::{DialogueRunner, RunnerError, Step};
+pub use source::{FsSource, MemorySource, SourceProvider};
+pub use transcript::{parse_transcripts, run_transcript, Transcript, TranscriptError};
 
 pub mod prelude {
     pub use crate::{
@@ -34,20 +73,124 @@ pub mod prelude {
     };
 }
 
-type Error = Box<dyn std::error::Error>;
+/// What compiled interactions get written as
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Packed bincode with a versioned header, the usual `.dgc`
+    #[default]
+    Bincode,
+
+    Json,
+    Ron,
 
-pub(crate) static SILENT: OnceLock<bool> = OnceLock::new();
+    /// MessagePack, with field names kept in
+    #[value(name = "msgpack")]
+    MessagePack,
+}
 
-macro_rules! log {
-    ($($arg:tt)*) => {
-        if !SILENT.get().unwrap_or(&false) {
-            eprintln!($($arg)*);
+impl OutputFormat {
+    /// Which format a compiled file is in, going by its
+    /// extension, or `None` if it doesn't look compiled
+    pub fn from_extension(path: impl AsRef<Path>) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "dgc" => Some(Self::Bincode),
+            "json" => Some(Self::Json),
+            "ron" => Some(Self::Ron),
+            "msgpack" | "mpk" => Some(Self::MessagePack),
+            _ => None,
         }
-    };
+    }
 }
 
+/// Serialize interactions in any of the output formats.
+///
+/// Interactions are written sorted by ID, so compiling the
+/// same file twice gives the exact same bytes.
+pub fn serialize(map: &InteractionMap, format: OutputFormat) -> Result<Vec<u8>, Error> {
+    // `pack` sorts them on its own
+    let sorted = || dump::sorted(map);
+
+    match format {
+        OutputFormat::Bincode => pack(map, false),
+        OutputFormat::Json => {
+            serde_json::to_vec_pretty(&sorted()).map_err(|e| Error::Serialize(e.into()))
+        }
+
+        OutputFormat::Ron => ron::ser::to_string_pretty(&sorted(), Default::default())
+            .map(String::into_bytes)
+            .map_err(|e| Error::Serialize(e.into())),
+
+        OutputFormat::MessagePack => {
+            rmp_serde::to_vec_named(&sorted()).map_err(|e| Error::Serialize(e.into()))
+        }
+    }
+}
+
+/// Deserialize a `.dgc` compiled as bincode.
+///
+/// Fails with `Error::Container` if it was compiled by a newer
+/// version of `dialogical` that this one can't read, or if the
+/// file is corrupted.
 pub fn deserialize(data: &[u8]) -> Result<InteractionMap, Error> {
-    bincode::deserialize(data).map_err(Into::into)
+    let (_, payload) = unpack(data)?;
+    bincode::deserialize(payload).map_err(|e| Error::Deserialize(e))
+}
+
+pub fn deserialize_json(data: &[u8]) -> Result<InteractionMap, Error> {
+    serde_json::from_slice(data).map_err(|e| Error::Deserialize(e.into()))
+}
+
+pub fn deserialize_ron(data: &[u8]) -> Result<InteractionMap, Error> {
+    ron::de::from_bytes(data).map_err(|e| Error::Deserialize(e.into()))
+}
+
+pub fn deserialize_msgpack(data: &[u8]) -> Result<InteractionMap, Error> {
+    rmp_serde::from_slice(data).map_err(|e| Error::Deserialize(e.into()))
+}
+
+/// Deserialize interactions in any of the output formats
+pub fn deserialize_as(data: &[u8], format: OutputFormat) -> Result<InteractionMap, Error> {
+    match format {
+        OutputFormat::Bincode => deserialize(data),
+        OutputFormat::Json => deserialize_json(data),
+        OutputFormat::Ron => deserialize_ron(data),
+        OutputFormat::MessagePack => deserialize_msgpack(data),
+    }
+}
+
+/// Read and parse one `.dg` file, along with anything it imports.
+///
+/// Fails with `Error::Parse` holding every error in the file,
+/// not just the first one.
+pub fn parse_file(path: impl AsRef<Path>) -> Result<InteractionMap, Error> {
+    parse_file_with_deps(path).0
+}
+
+/// Same as `parse_file`, but also gives back every file that
+/// was read by `Import` or `Execute` along the way. Those are
+/// there even if parsing failed, since fixing the error might
+/// mean changing one of them.
+pub fn parse_file_with_deps(
+    path: impl AsRef<Path>,
+) -> (Result<InteractionMap, Error>, Vec<PathBuf>) {
+    parse_file_using(DgParser::new(path.as_ref().to_owned()))
+}
+
+/// Same as `parse_file_with_deps`, but with a parser you've
+/// already set up, like with [`DgParser::include_dirs`]
+pub fn parse_file_using(mut parser: DgParser) -> (Result<InteractionMap, Error>, Vec<PathBuf>) {
+    let data = match parser.read_entry() {
+        Ok(data) => data,
+        Err(e) => return (Err(Error::Read(parser.path().to_owned(), e)), vec![]),
+    };
+
+    let (res, errors) = parser.parse_all_recovering(&data);
+    let deps = parser.dependencies();
+
+    match errors.is_empty() {
+        true => (Ok(res), deps),
+        false => (Err(ParseErrors(errors).into()), deps),
+    }
 }
 
 /// Compile one `.dg` file into a packed `.dgc` via a simple
@@ -55,68 +198,230 @@ pub fn deserialize(data: &[u8]) -> Result<InteractionMap, Error> {
 /// CLI version. Reasonable defaults, but you can always use
 /// `cli_main` directly if you need more control.
 pub fn compile(entry: &str, out: &str) -> Result<(), Error> {
-    let args = Args {
-        file: Some(entry.into()),
-        output: Some(out.into()),
-        silent: true,
+    compile_as(entry, out, OutputFormat::Bincode)
+}
+
+/// Same as `compile`, but in whichever output format you want
+pub fn compile_as(entry: &str, out: &str, format: OutputFormat) -> Result<(), Error> {
+    let options = OutputArgs {
+        format,
+        ..Default::default()
     };
 
-    cli_main(args, None)
+    let (res, _) = compile_using(DgParser::new(entry.into()), &options)?;
+    fs::write(out, res).map_err(|e| Error::Write(out.into(), e))
 }
 
-pub fn cli_main(args: Args, cwd: Option<&Path>) -> Result<(), Error> {
-    SILENT.set(args.silent).unwrap();
-
-    // TODO error handling for file rw
-    let input_stream: Box<dyn Read> = match args.file {
-        Some(ref file) => Box::new(File::open(file).unwrap()),
-        None => Box::new(io::stdin()),
+/// Same as `compile_as`, but also writes a depfile listing
+/// `entry` and every file it imports next to `out`. Gives
+/// back the path of the depfile.
+pub fn compile_with_depfile(
+    entry: &str,
+    out: &str,
+    format: OutputFormat,
+    depfile: DepfileFormat,
+) -> Result<PathBuf, Error> {
+    let options = OutputArgs {
+        format,
+        ..Default::default()
     };
 
-    let mut output_stream: Box<dyn Write> = match args.output {
-        Some(file) => Box::new(File::create(file).unwrap()),
-        None => Box::new(io::stdout()),
-    };
+    let (res, inputs) = compile_using(DgParser::new(entry.into()), &options)?;
+    fs::write(out, res).map_err(|e| Error::Write(out.into(), e))?;
+    write_depfile(out.as_ref(), &inputs, depfile)
+}
 
-    log!("Reading...");
-    let data = io::read_to_string(input_stream)?;
+/// What `dg compile` does, with a parser you've already set
+/// up and every output option it has. Gives back the compiled
+/// bytes, along with the entry file and everything it imports
+/// for writing a depfile.
+pub fn compile_using(
+    parser: DgParser,
+    options: &OutputArgs,
+) -> Result<(Vec<u8>, Vec<PathBuf>), Error> {
+    let entry = parser.path().to_owned();
+    let (res, deps) = parse_file_using(parser);
+    let res = options.compile(res?)?;
 
-    // priority:
-    // 1. path is the cwd argument passed in, if any
-    // 2. if file argument, `path` is the path of the file
-    // 3. if reading stdin, `path` is the current dir
-    log!("Parsing...");
-    let path = cwd
-        .map(PathBuf::from)
-        .or_else(|| args.file.as_ref().map(PathBuf::from))
-        .unwrap_or_else(|| std::env::current_dir().unwrap());
+    let inputs = std::iter::once(entry).chain(deps).collect();
+    Ok((res, inputs))
+}
 
-    let mut parser = DgParser::new(path);
-    let res = parser.parse_all(&data)?;
+/// Parse a `.dg` file and make sure every goto in it leads
+/// to an interaction that exists, failing with
+/// `Error::DanglingGotos` if any don't.
+///
+/// Also runs `analyze_reachability` from the `entries` given,
+/// and returns the results. Those are only warnings, so they
+/// won't make this fail.
+pub fn check(entry: &str, entries: &[String]) -> Result<Reachability, Error> {
+    check_using(DgParser::new(entry.into()), entries)
+}
 
-    log!("Serializing...");
-    let res = bincode::serialize(&res)?;
+/// Same as `check`, but with a parser you've already set up
+pub fn check_using(parser: DgParser, entries: &[String]) -> Result<Reachability, Error> {
+    let res = parse_file_using(parser).0?;
 
-    log!("Writing...");
-    output_stream.write(&res)?;
+    let dangling = check_gotos(&res);
+    if !dangling.is_empty() {
+        return Err(DanglingGotos(dangling).into());
+    }
 
-    log!("Done!");
-    Ok(())
+    Ok(analyze_reachability(&res, entries))
 }
 
-#[derive(Parser, Debug)]
-#[command(arg_required_else_help(true))]
-#[command(author, version, about)]
-/// P/E/T/S Dialogue Compiler
-pub struct Args {
-    /// The output file, or stdout if not specified
-    #[arg(short, long)]
-    output: Option<String>,
+/// Parse a `.dg` file and draw a graph of which
+/// interactions lead to which
+pub fn graph(entry: &str, format: GraphFormat) -> Result<String, Error> {
+    graph_using(DgParser::new(entry.into()), format)
+}
+
+/// Same as `graph`, but with a parser you've already set up
+pub fn graph_using(parser: DgParser, format: GraphFormat) -> Result<String, Error> {
+    let res = parse_file_using(parser).0?;
+    Ok(render_graph(&res, format))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dummy_file(name: &str) -> String {
+        format!("{}/dummy_data/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    fn temp_file(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("dialogical-{}-{}", std::process::id(), name));
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn compile_twice() {
+        let out = temp_file("compile_twice.dgc");
+
+        compile(&dummy_file("rodrick.dg"), &out).unwrap();
+        compile(&dummy_file("rodrick.dg"), &out).unwrap();
+
+        let compiled = deserialize(&fs::read(&out).unwrap()).unwrap();
+        assert_eq!(compiled, parse_file(dummy_file("rodrick.dg")).unwrap());
+
+        fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn every_format_round_trips() {
+        let map = parse_file(dummy_file("rodrick.dg")).unwrap();
+
+        for format in OutputFormat::value_variants() {
+            let data = serialize(&map, *format).unwrap();
+            assert_eq!(deserialize_as(&data, *format).unwrap(), map, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn format_from_extension() {
+        assert_eq!(
+            OutputFormat::from_extension("a.dgc"),
+            Some(OutputFormat::Bincode)
+        );
+        assert_eq!(
+            OutputFormat::from_extension("a/b.json"),
+            Some(OutputFormat::Json)
+        );
+        assert_eq!(
+            OutputFormat::from_extension("a.mpk"),
+            Some(OutputFormat::MessagePack)
+        );
+        assert_eq!(OutputFormat::from_extension("a.dg"), None);
+        assert_eq!(OutputFormat::from_extension("dgc"), None);
+    }
+
+    #[test]
+    fn compile_as_json() {
+        let out = temp_file("compile_as.json");
+        compile_as(&dummy_file("pageonly.dg"), &out, OutputFormat::Json).unwrap();
+
+        let compiled = deserialize_json(&fs::read(&out).unwrap()).unwrap();
+        assert_eq!(compiled, parse_file(dummy_file("pageonly.dg")).unwrap());
+
+        fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn serialize_is_stable() {
+        let map = parse_file(dummy_file("rodrick.dg")).unwrap();
+        let first = serialize(&map, OutputFormat::Bincode).unwrap();
+
+        // same interactions, inserted the other way around
+        let mut entries = map.clone().into_iter().collect::<Vec<_>>();
+        entries.sort_by(|a, b| b.0.cmp(&a.0));
+        let reversed = entries.into_iter().collect::<InteractionMap>();
+
+        assert_eq!(reversed, map);
+        assert_eq!(serialize(&reversed, OutputFormat::Bincode).unwrap(), first);
+    }
+
+    #[test]
+    fn compile_depfile() {
+        let out = temp_file("compile_depfile.dgc");
+        let entry = dummy_file("import_sub.dg");
+
+        let depfile =
+            compile_with_depfile(&entry, &out, OutputFormat::Bincode, DepfileFormat::Make).unwrap();
+
+        let data = fs::read_to_string(&depfile).unwrap();
+        let (rule, _) = data.split_once('\n').unwrap();
+
+        assert!(rule.starts_with(&format!("{}: {} ", out, entry)));
+        // the target, the entry, and the 6 files it imports
+        assert_eq!(rule.split_whitespace().count(), 8);
+
+        fs::remove_file(out).unwrap();
+        fs::remove_file(depfile).unwrap();
+    }
+
+    #[test]
+    fn compile_with_every_option() {
+        let entry = dummy_file("chapters/include_dir.dg");
+        let parser =
+            DgParser::new(entry.clone().into()).include_dirs([dummy_file("shared").into()]);
+        let options = OutputArgs {
+            toc: true,
+            resolve_metadata: true,
+            ..Default::default()
+        };
+
+        let (res, inputs) = compile_using(parser, &options).unwrap();
+        let header = read_header(&res).unwrap().unwrap();
+
+        assert!(header.toc.is_some());
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(inputs[0], Path::new(&entry));
+        assert!(inputs[1].ends_with("shared/npcs.dg"));
+    }
+
+    #[test]
+    fn compile_missing_input() {
+        let entry = dummy_file("does_not_exist.dg");
+        let err = compile(&entry, &temp_file("missing_input.dgc")).unwrap_err();
+
+        assert!(matches!(err, Error::Read(path, _) if path == Path::new(&entry)));
+    }
+
+    #[test]
+    fn compile_unwritable_output() {
+        let out = dummy_file("not_a_dir.dg/out.dgc");
+        let err = compile(&dummy_file("rodrick.dg"), &out).unwrap_err();
 
-    /// The input file, or stdin if not specified
-    file: Option<String>,
+        assert!(matches!(err, Error::Write(path, _) if path == Path::new(&out)));
+    }
 
-    /// Silences progress "info" stderr messages.
-    #[arg(short, long)]
-    silent: bool,
+    #[test]
+    fn compile_parse_errors() {
+        let err = compile(
+            &dummy_file("many_errors.dg"),
+            &temp_file("parse_errors.dgc"),
+        );
+        assert!(matches!(err, Err(Error::Parse(ParseErrors(errors))) if errors.len() == 3));
+    }
 }
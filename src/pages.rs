@@ -3,6 +3,8 @@
 //!
 
 use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -39,8 +41,62 @@ pub enum ChoicesState {
     Choices,
 }
 
+/// A location in a `.dg` source file
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub path: PathBuf,
+
+    /// 1-indexed line number
+    pub line: usize,
+
+    /// 1-indexed column number, counted in characters
+    pub col: usize,
+}
+
+impl Span {
+    pub fn new(path: PathBuf, line: usize, col: usize) -> Self {
+        Self { path, line, col }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.path.display(), self.line, self.col)
+    }
+}
+
+/// A `ParseErrorKind` along with where it happened
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Span,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.kind)
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.kind.source()
+    }
+}
+
+/// Every error from a parse that kept going after the first one
 #[derive(Debug, Error, PartialEq)]
-pub enum ParseError {
+#[error("Parsing failed with {} error(s)", .0.len())]
+pub struct ParseErrors(pub Vec<ParseError>);
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ParseErrorKind {
     #[error("Encountered {0} while trying to parse interaction endings...")]
     MalformedEnding(String),
 
@@ -65,17 +121,18 @@ pub enum ParseError {
     #[error("No interaction to push onto the parser's list!")]
     PushEmptyIX,
 
-    #[error("Attempt to push a duplicate interaction")]
-    PushDuplicateIX,
+    /// The ID, and the file it was first defined in
+    #[error("Interaction {0} is already defined in {}", .1.display())]
+    PushDuplicateIX(String, PathBuf),
 
     #[error("Attempt to push a page after an ending in interaction")]
     PageAfterEnding,
 
     #[error("Failed while running comptime script")]
-    Panic(ScriptError),
+    Panic(#[source] ScriptError),
 }
 
-impl From<ScriptError> for ParseError {
+impl From<ScriptError> for ParseErrorKind {
     fn from(value: ScriptError) -> Self {
         Self::Panic(value)
     }
@@ -109,11 +166,55 @@ impl Interaction {
                 Ok(())
             }
 
-            Label(l) => Err(ParseError::MixedEndings(l.to_string())),
+            Label(l) => Err(ParseErrorKind::MixedEndings(l.to_string())),
+        }
+    }
+
+    /// Every page, with the speaker and vox that are in effect
+    /// on it instead of whatever changes its metadata made.
+    ///
+    /// `Permanent` changes stick around until the next one,
+    /// `PageOnly` changes only affect their own page, and
+    /// `NoChange` keeps whatever was there before. Before any
+    /// changes, the speaker is the narrator and vox is empty.
+    pub fn resolved_pages(&self) -> Vec<ResolvedPage> {
+        let mut speaker = Speaker::default();
+        let mut vox = String::new();
+
+        self.pages
+            .iter()
+            .map(|page| ResolvedPage {
+                speaker: page.metadata.speaker.resolve(&mut speaker).clone(),
+                vox: page.metadata.vox.resolve(&mut vox).clone(),
+                content: page.content.clone(),
+            })
+            .collect()
+    }
+
+    /// Replace every page's metadata with `Permanent` lines
+    /// holding whatever is in effect on that page, so it can
+    /// be read without going through `resolved_pages`
+    pub fn resolve_metadata(&mut self) {
+        let resolved = self.resolved_pages();
+
+        for (page, resolved) in self.pages.iter_mut().zip(resolved) {
+            page.metadata = PageMeta {
+                speaker: Metaline::Permanent(resolved.speaker),
+                vox: Metaline::Permanent(resolved.vox),
+            };
         }
     }
 }
 
+/// A page, with the speaker and vox that are in effect on it.
+/// See `Interaction::resolved_pages`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResolvedPage {
+    pub speaker: Speaker,
+    pub vox: String,
+    pub content: String,
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Page {
     pub metadata: PageMeta,
@@ -142,6 +243,16 @@ pub enum Speaker {
     Unknown,
 }
 
+impl fmt::Display for Speaker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Named(name) => write!(f, "{}", name),
+            Self::Narrator => Ok(()),
+            Self::Unknown => write!(f, "???"),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct PageMeta {
     pub speaker: Metaline<Speaker>,
@@ -197,8 +308,100 @@ impl<T> Metaline<T> {
         }
     }
 
+    /// Apply this line on top of `current`, and get
+    /// the value in effect for the page it's on
+    pub fn resolve<'a>(&'a self, current: &'a mut T) -> &'a T
+    where
+        T: Clone,
+    {
+        match self {
+            Self::Permanent(val) => {
+                *current = val.clone();
+                current
+            }
+
+            Self::PageOnly(val) => val,
+            Self::NoChange => current,
+        }
+    }
+
     /// shorthand to "just get it done"
     pub fn unwrap(&self) -> &T {
         self.try_unwrap().unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::parse_dummy;
+
+    use pretty_assertions::assert_eq;
+
+    fn resolved(speaker: Speaker, vox: &str, content: &str) -> ResolvedPage {
+        ResolvedPage {
+            speaker,
+            vox: vox.to_owned(),
+            content: content.to_owned(),
+        }
+    }
+
+    #[test]
+    fn resolve_pageonly() {
+        let map = parse_dummy("pageonly.dg");
+        let mira = || Speaker::Named("Mira".to_owned());
+
+        assert_eq!(
+            map["PageOnly Test"].resolved_pages(),
+            vec![
+                resolved(mira(), "Mira", "What's up?"),
+                resolved(mira(), "Ethan", "Nothing much..."),
+                resolved(
+                    mira(),
+                    "Mira",
+                    "Alright, why am I talking to myself?\nWho's making me do this?"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_defaults() {
+        let ix = Interaction {
+            pages: vec![
+                Page::from_content("Nobody here".to_owned()),
+                Page {
+                    metadata: PageMeta {
+                        speaker: Metaline::PageOnly(Speaker::Unknown),
+                        vox: Metaline::Permanent("Wind".to_owned()),
+                    },
+                    content: "...or is there?".to_owned(),
+                },
+                Page::from_content("Guess not".to_owned()),
+            ],
+            ending: DialogueEnding::End,
+        };
+
+        assert_eq!(
+            ix.resolved_pages(),
+            vec![
+                resolved(Speaker::Narrator, "", "Nobody here"),
+                resolved(Speaker::Unknown, "Wind", "...or is there?"),
+                resolved(Speaker::Narrator, "Wind", "Guess not"),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_metadata_in_place() {
+        let mut ix = parse_dummy("pageonly.dg").remove("PageOnly Test").unwrap();
+        let before = ix.resolved_pages();
+        ix.resolve_metadata();
+
+        assert_eq!(ix.resolved_pages(), before);
+        assert_eq!(
+            ix.pages[2].metadata.vox,
+            Metaline::Permanent("Mira".to_owned())
+        );
+    }
+}
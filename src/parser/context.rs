@@ -1,4 +1,8 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
 use crate::comptime::{ScriptOutput, Unlink};
+use crate::source::Sources;
 use crate::{InteractionMap, Link, LinkKVPair};
 
 /// Wrapper type around `Vec<ScriptOutput>`.
@@ -6,19 +10,66 @@ use crate::{InteractionMap, Link, LinkKVPair};
 /// Any result of comptime execution that affects
 /// how the parser does its job goes in here...
 #[derive(Debug, Default, PartialEq)]
-pub struct ScriptContext(pub Vec<ScriptOutput>);
+pub struct ScriptContext {
+    pub outputs: Vec<ScriptOutput>,
+
+    /// Files being imported right now, outermost first,
+    /// for catching files that end up importing themselves
+    pub(crate) import_chain: Vec<PathBuf>,
+
+    /// Every file imported so far, anywhere in the tree,
+    /// so the same file isn't imported twice
+    pub(crate) imported: HashSet<PathBuf>,
+
+    /// Whether an `Import` ran since the last interaction was
+    /// pushed, even one that had nothing new to give back
+    pub(crate) imported_since_push: bool,
+
+    /// Every interaction ID defined so far, here or in an
+    /// imported file, and which file it's from
+    pub(crate) origins: HashMap<String, PathBuf>,
+
+    /// Where else to look for imported files
+    pub(crate) include_dirs: Vec<PathBuf>,
+
+    /// What `@/` paths are relative to
+    pub(crate) root: Option<PathBuf>,
+
+    /// Where imported files get read from
+    pub(crate) sources: Sources,
+}
 
 #[allow(unused)] // STFU!!!!
 impl ScriptContext {
     pub fn log(&mut self, msg: &str) {
-        self.0.push(ScriptOutput::LogMessage(msg.to_owned()));
+        self.outputs.push(ScriptOutput::LogMessage(msg.to_owned()));
+    }
+
+    /// Remember that a file was read, unless it already was
+    pub fn depend(&mut self, path: &Path) {
+        if !self.iter_dependencies().any(|v| v == path) {
+            self.outputs.push(ScriptOutput::Dependency(path.to_owned()));
+        }
+    }
+
+    /// Every file read by `Import` or `Execute`, in the
+    /// order they were first read
+    pub fn iter_dependencies(&self) -> impl Iterator<Item = &Path> {
+        self.outputs.iter().filter_map(|v| match v {
+            ScriptOutput::Dependency(path) => Some(path.as_path()),
+            _ => None,
+        })
+    }
+
+    pub fn dependencies(&self) -> Vec<PathBuf> {
+        self.iter_dependencies().map(Path::to_owned).collect()
     }
 
     pub fn drain_interactions(&mut self) -> InteractionMap {
         let mut interactions = InteractionMap::new();
 
         // TODO probably a better way to do this
-        self.0.retain(|output| match output {
+        self.outputs.retain(|output| match output {
             ScriptOutput::Interaction(ix_id, ix) => {
                 interactions.insert(ix_id.to_owned(), ix.clone());
                 false
@@ -35,7 +86,7 @@ impl ScriptContext {
             return;
         }
 
-        self.0.push(ScriptOutput::Link(link));
+        self.outputs.push(ScriptOutput::Link(link));
     }
 
     pub fn unlink(&mut self, unlink: &Unlink) {
@@ -54,7 +105,7 @@ impl ScriptContext {
 
     /// Delete any links with empty associations
     pub fn clean_links(&mut self) {
-        self.0.retain(|output| match output {
+        self.outputs.retain(|output| match output {
             ScriptOutput::Link(link) => !link.associations.is_empty(),
             _ => true,
         });
@@ -68,7 +119,7 @@ impl ScriptContext {
 
     /// Iterator over all the log messages
     pub fn iter_logs(&self) -> impl Iterator<Item = &str> {
-        self.0.iter().filter_map(|v| match v {
+        self.outputs.iter().filter_map(|v| match v {
             ScriptOutput::LogMessage(msg) => Some(msg.as_str()),
             _ => None,
         })
@@ -79,14 +130,14 @@ impl ScriptContext {
     }
 
     pub fn iter_links(&self) -> impl Iterator<Item = &Link> {
-        self.0.iter().filter_map(|v| match v {
+        self.outputs.iter().filter_map(|v| match v {
             ScriptOutput::Link(link) => Some(link),
             _ => None,
         })
     }
 
     pub fn iter_links_mut(&mut self) -> impl Iterator<Item = &mut Link> {
-        self.0.iter_mut().filter_map(|v| match v {
+        self.outputs.iter_mut().filter_map(|v| match v {
             ScriptOutput::Link(link) => Some(link),
             _ => None,
         })
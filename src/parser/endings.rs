@@ -8,7 +8,7 @@ use serde::{Deserialize, Serialize};
 use std::fmt;
 
 use crate::consts::*;
-use crate::pages::{ParseError, ParseState};
+use crate::pages::{ParseErrorKind, ParseState};
 use crate::{DgParser, ParseResult};
 
 /// One choice in a list of dialogue choices
@@ -55,6 +55,33 @@ pub enum DialogueEnding {
 }
 
 impl DialogueEnding {
+    /// Every label this ending can go to, each with the index
+    /// of the choice it belongs to (`None` for a lone goto)
+    pub fn labels(&self) -> Vec<(Option<usize>, &Label)> {
+        match self {
+            Self::Choices(choices) => choices
+                .iter()
+                .enumerate()
+                .filter_map(|(i, choice)| Some((Some(i), choice.label.as_ref()?)))
+                .collect(),
+
+            Self::Label(label) => vec![(None, label)],
+            Self::End => vec![],
+        }
+    }
+
+    /// Same as `labels`, but for changing where they go
+    pub fn labels_mut(&mut self) -> Vec<&mut Label> {
+        match self {
+            Self::Choices(choices) => choices
+                .iter_mut()
+                .filter_map(|v| v.label.as_mut())
+                .collect(),
+            Self::Label(label) => vec![label],
+            Self::End => vec![],
+        }
+    }
+
     pub fn append_choice(&mut self, choice: DialogueChoice) -> ParseResult<()> {
         match self {
             DialogueEnding::Choices(ref mut choices) => {
@@ -65,7 +92,7 @@ impl DialogueEnding {
                 *self = DialogueEnding::Choices(vec![choice]);
             }
 
-            _ => return Err(ParseError::MixedEndings(choice.text.clone())),
+            _ => return Err(ParseErrorKind::MixedEndings(choice.text.clone())),
         }
         Ok(())
     }
@@ -93,7 +120,7 @@ pub fn parse_choice(parser: &mut DgParser, line: &str) -> ParseResult<()> {
 
         let first_ch = it
             .next()
-            .ok_or(ParseError::MalformedEnding(line.to_owned()))?;
+            .ok_or(ParseErrorKind::MalformedEnding(line.to_owned()))?;
 
         it.next(); // skip the space
         (first_ch, it.as_str())
@@ -102,7 +129,7 @@ pub fn parse_choice(parser: &mut DgParser, line: &str) -> ParseResult<()> {
     let ix = parser
         .interaction
         .as_mut()
-        .ok_or(ParseError::PushPageNoIX)?;
+        .ok_or(ParseErrorKind::PushPageNoIX)?;
     match first_ch {
         PREFIX_CHOICE => {
             // parse a choice
@@ -120,24 +147,24 @@ pub fn parse_choice(parser: &mut DgParser, line: &str) -> ParseResult<()> {
         _ => {
             let label = match first_ch {
                 PREFIX_GOTO_LABEL => Label::new_goto(rest),
-                _ => return Err(ParseError::MalformedEnding(line.to_owned())),
+                _ => return Err(ParseErrorKind::MalformedEnding(line.to_owned())),
             };
 
             match ix.ending {
                 DialogueEnding::Choices(ref mut choices) => {
                     let choice = choices
                         .last_mut()
-                        .ok_or_else(|| ParseError::MalformedEnding(line.to_owned()))?;
+                        .ok_or_else(|| ParseErrorKind::MalformedEnding(line.to_owned()))?;
 
                     if choice.label.is_some() {
-                        return Err(ParseError::MixedEndings(line.to_owned()));
+                        return Err(ParseErrorKind::MixedEndings(line.to_owned()));
                     }
 
                     choice.label = Some(label);
                 }
 
                 DialogueEnding::Label(_) => {
-                    return Err(ParseError::MixedEndings(line.to_owned()));
+                    return Err(ParseErrorKind::MixedEndings(line.to_owned()));
                 }
 
                 DialogueEnding::End => {
@@ -1,13 +1,13 @@
 use crate::comptime::LinkKVPair;
 use crate::consts::COMPTIME_BORDER;
-use crate::pages::{Metaline, ParseError, ParseState, Speaker};
+use crate::pages::{Metaline, ParseErrorKind, ParseState, Speaker};
 
 use super::{DgParser, Result};
 
 /// split into first "word" and the rest
 fn split_first_whitespace(full: &str) -> Result<(&str, &str)> {
     full.split_once(char::is_whitespace)
-        .ok_or(ParseError::NotMeta(full.to_string()))
+        .ok_or(ParseErrorKind::NotMeta(full.to_string()))
         .map(|(k, v)| (k, v.trim_start()))
 }
 
@@ -24,6 +24,7 @@ pub fn parse(parser: &mut DgParser, line: &str) -> Result<()> {
         debug_assert!(!matches!(parser.state, ParseState::ComptimeScript(_)));
 
         parser.state = ParseState::ComptimeScript(Box::new(parser.state.clone()));
+        parser.comptime_line = parser.line;
         return Ok(());
     }
 
@@ -44,19 +45,16 @@ pub fn parse(parser: &mut DgParser, line: &str) -> Result<()> {
         return parser.set_ix_id(kv.1);
     }
 
-    match kv.1 {
-        k @ ("_" | "?") => {
-            let speaker = if k == "_" {
-                Speaker::Narrator
-            } else {
-                Speaker::Unknown
-            };
-
-            parser.page.metadata.speaker = Metaline::new(speaker, pageonly);
-            return Ok(());
-        }
+    // only for names, since a voice could really be called `_`
+    if let ("NAME", k @ ("_" | "?")) = kv {
+        let speaker = if k == "_" {
+            Speaker::Narrator
+        } else {
+            Speaker::Unknown
+        };
 
-        _ => {}
+        parser.page.metadata.speaker = Metaline::new(speaker, pageonly);
+        return Ok(());
     }
 
     // the pair + any pairs linked using the `Link` directive
@@ -75,7 +73,7 @@ pub fn parse(parser: &mut DgParser, line: &str) -> Result<()> {
             "VOX" => parser.page.metadata.vox = Metaline::new(val.to_owned(), pageonly),
 
             _ => {
-                return Err(ParseError::InvalidMeta(line.to_string()));
+                return Err(ParseErrorKind::InvalidMeta(line.to_string()));
             }
         };
     }
@@ -2,20 +2,24 @@
 //! Stuff for parsing dg files
 //!
 
-pub type Result<T> = std::result::Result<T, ParseError>;
+pub type Result<T> = std::result::Result<T, ParseErrorKind>;
 
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use crate::comptime::{Script, ScriptPath};
 use crate::consts::{COMPTIME_BORDER, SEPARATOR};
-use crate::pages::{ChoicesState, Interaction, Page, ParseState};
+use crate::pages::{ChoicesState, Interaction, Page, ParseState, Span};
+use crate::source::{SourceProvider, Sources};
 use crate::InteractionMap;
 
 mod context;
 mod endings;
 mod metaline;
 
-pub use crate::pages::ParseError;
+pub use crate::pages::{ParseError, ParseErrorKind, ParseErrors};
 pub use context::ScriptContext;
 pub use endings::{DialogueChoice, DialogueEnding, Label};
 
@@ -33,48 +37,150 @@ pub struct DgParser {
     // TODO store these inside `ParseState`
     interaction: Option<Interaction>,
     ix_id: Option<String>,
+    ix_line: usize,
     comptime_script: Vec<String>,
+    comptime_line: usize,
     page: Page,
     pagebuf: Vec<String>,
     page_had_ending: bool,
+
+    /// 1-indexed number of the line currently being parsed
+    line: usize,
 }
 
 impl DgParser {
     pub fn new(path: PathBuf) -> Self {
+        let mut context = ScriptContext::default();
+        context
+            .import_chain
+            .push(context.sources.canonicalize(&path));
+        context.root = path.parent().map(Path::to_owned);
+
         Self {
             state: ParseState::default(),
-            context: ScriptContext::default(),
+            context,
             path,
 
             interactions: InteractionMap::new(),
             interaction: None,
             ix_id: None,
+            ix_line: 0,
             page: Page::default(),
             pagebuf: vec![],
             comptime_script: vec![],
+            comptime_line: 0,
+            line: 0,
             page_had_ending: false,
         }
     }
 
+    /// A parser for a file imported by whatever owns `parent`,
+    /// so it knows what's already been imported and by who
+    ///
+    /// `imported` is the files it should act like were already
+    /// imported, usually the same ones as `parent`.
+    pub(crate) fn imported_from(
+        path: PathBuf,
+        parent: &ScriptContext,
+        imported: HashSet<PathBuf>,
+    ) -> Self {
+        let mut res = Self::new(path);
+
+        res.context.import_chain = parent.import_chain.clone();
+        res.context
+            .import_chain
+            .push(parent.sources.canonicalize(&res.path));
+        res.context.imported = imported;
+        res.context.include_dirs = parent.include_dirs.clone();
+        res.context.root = parent.root.clone();
+        res.context.sources = parent.sources.clone();
+        res
+    }
+
+    /// A parser for the same file that hasn't parsed anything
+    /// yet, looking for imports in the same places
+    pub(crate) fn fresh(&self) -> Self {
+        let mut res = Self::new(self.path.clone());
+
+        res.context.import_chain = vec![self.context.sources.canonicalize(&self.path)];
+        res.context.include_dirs = self.context.include_dirs.clone();
+        res.context.root = self.context.root.clone();
+        res.context.sources = self.context.sources.clone();
+        res
+    }
+
+    /// Read imported files from somewhere other than the disk
+    pub fn sources(mut self, sources: impl SourceProvider + 'static) -> Self {
+        self.context.sources = Sources(Arc::new(sources));
+        self.context.import_chain = vec![self.context.sources.canonicalize(&self.path)];
+        self
+    }
+
+    /// Read the file being parsed from wherever
+    /// imported files are being read from
+    pub fn read_entry(&self) -> io::Result<String> {
+        self.context.sources.read(&self.path)
+    }
+
+    /// Also look for imported files in these folders, in order,
+    /// if they're not next to the file importing them
+    pub fn include_dirs(mut self, dirs: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.context.include_dirs.extend(dirs);
+        self
+    }
+
+    /// Make `@/` paths relative to this folder, instead
+    /// of the one the entry file is in
+    pub fn project_root(mut self, root: PathBuf) -> Self {
+        self.context.root = Some(root);
+        self
+    }
+
+    /// The file being parsed
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Every file imported by this parser so far, including
+    /// ones it was told about in [`Self::imported_from`]
+    pub(crate) fn imported(&self) -> &HashSet<PathBuf> {
+        &self.context.imported
+    }
+
+    /// Which file each interaction parsed so far came from,
+    /// including ones that were imported
+    pub(crate) fn origins(&self) -> &HashMap<String, PathBuf> {
+        &self.context.origins
+    }
+
     fn set_ix_id(&mut self, id: &str) -> Result<()> {
         if self.interaction.is_some() {
             self.push_ix()?;
         }
 
         self.ix_id = Some(id.to_owned());
+        self.ix_line = self.line;
         self.interaction = Some(Interaction::default());
 
         Ok(())
     }
 
-    fn parse_comptime(&mut self, line: &str) -> Result<()> {
+    /// Takes the untrimmed line, so the script can
+    /// report accurate columns in its errors
+    fn parse_comptime(&mut self, raw: &str) -> Result<()> {
+        let line = raw.trim();
+        let last_is_border = self
+            .comptime_script
+            .last()
+            .is_some_and(|v| v.trim() == COMPTIME_BORDER);
+
         // if current line is the closing `---`
-        if line == SEPARATOR && self.comptime_script.last() == Some(&COMPTIME_BORDER.to_owned()) {
+        if line == SEPARATOR && last_is_border {
             self.comptime_script.pop();
 
             let content = self.comptime_script.join("\n");
             let path = ScriptPath(self.path.clone());
-            let mut script = Script::new(content, path);
+            let mut script = Script::new(content, path).starting_at(self.comptime_line);
             script.execute(&mut self.context)?;
 
             // TODO no `self.script`, make the enum variant
@@ -86,7 +192,7 @@ impl DgParser {
                 _ => unreachable!(),
             };
         } else {
-            self.comptime_script.push(line.to_owned());
+            self.comptime_script.push(raw.to_owned());
         }
 
         Ok(())
@@ -128,14 +234,17 @@ impl DgParser {
             res
         };
 
-        let ix = self.interaction.as_mut().ok_or(ParseError::PushPageNoIX)?;
+        let ix = self
+            .interaction
+            .as_mut()
+            .ok_or(ParseErrorKind::PushPageNoIX)?;
 
         // you may not add another page after an ending
         // for more info, see <https://github.com/Lamby777/dialogical/issues/3>
         let ix_has_ending_yet = ix.ending != DialogueEnding::End;
         if ix_has_ending_yet {
             if self.page_had_ending {
-                return Err(ParseError::PageAfterEnding);
+                return Err(ParseErrorKind::PageAfterEnding);
             }
 
             // "poisons" the current interaction so it remembers
@@ -158,56 +267,182 @@ impl DgParser {
     fn push_ix(&mut self) -> Result<()> {
         let ix_id = self.ix_id.take();
         let ix = self.interaction.take();
-        let comptime_imports = self.context.drain_interactions();
+        self.page_had_ending = false;
 
-        if let (Some(ix_id), Some(ix)) = (ix_id, ix) {
-            if self.interactions.contains_key(&ix_id) {
-                return Err(ParseError::PushDuplicateIX);
-            }
+        // check before draining the imports, so they don't
+        // get thrown out along with a duplicate interaction
+        let res = match (ix_id, ix) {
+            (Some(ix_id), Some(ix)) => match self.context.origins.get(&ix_id) {
+                Some(first) => Err(ParseErrorKind::PushDuplicateIX(ix_id, first.clone())),
+
+                None => {
+                    self.context
+                        .origins
+                        .insert(ix_id.clone(), self.path.clone());
+
+                    self.interactions.insert(ix_id, ix);
+                    Ok(())
+                }
+            },
 
-            self.interactions.insert(ix_id, ix);
-        } else if comptime_imports.is_empty() {
             // empty ix are not allowed... UNLESS there are
-            // imports in a comptime script inside it
-            return Err(ParseError::PushEmptyIX);
-        }
+            // imports in a comptime script inside it, even
+            // ones of files that were already imported
+            _ if !self.context.imported_since_push => Err(ParseErrorKind::PushEmptyIX),
+            _ => Ok(()),
+        };
 
         // push any interactions imported from comptime scripts
+        self.context.imported_since_push = false;
+        let comptime_imports = self.context.drain_interactions();
         self.interactions.extend(comptime_imports);
 
-        self.page_had_ending = false;
+        res
+    }
 
-        Ok(())
+    /// Where the parser currently is in the file
+    fn span_at(&self, col: usize) -> Span {
+        Span::new(self.path.clone(), self.line, col)
+    }
+
+    /// Attach a location to an error. Script errors already
+    /// know which line of the script they came from, and
+    /// duplicates are reported at the offending `%` header
+    /// instead of wherever the parser noticed them.
+    fn locate(&self, kind: ParseErrorKind, col: usize) -> ParseError {
+        let span = match kind {
+            ParseErrorKind::Panic(ref e) => e.span.clone(),
+            ParseErrorKind::PushDuplicateIX(..) => Span::new(self.path.clone(), self.ix_line, 1),
+            _ => self.span_at(col),
+        };
+
+        ParseError::new(kind, span)
     }
 
-    pub fn parse_all(&mut self, data: &str) -> Result<InteractionMap> {
-        let lines = data.lines();
+    fn parse_line(&mut self, raw: &str) -> Result<()> {
+        use ParseState::*;
+
+        let line = raw.trim();
+
+        match self.state {
+            // besides the start, a block can either be
+            // a comptime script or a message section
+            ComptimeScript(_) => self.parse_comptime(raw),
+
+            Metadata => metaline::parse(self, line),
+            Message => self.parse_message(line),
+            Choices(ChoicesState::Choices) => endings::parse_choice(self, line),
+        }
+    }
 
+    /// Throw away whatever was half-parsed when an error
+    /// happened on `line`.
+    ///
+    /// Returns `true` if lines need to be skipped until the
+    /// next `---` or `%` header before parsing can continue.
+    fn discard(&mut self, line: &str) -> bool {
         self.pagebuf.clear();
         self.page = Page::default();
+        self.comptime_script.clear();
 
-        for line in lines {
-            use ParseState::*;
+        if line == SEPARATOR {
+            self.state = ParseState::Metadata;
+            return false;
+        }
 
-            let line = line.trim();
+        if is_ix_header(line) {
+            // the interaction before it got thrown out, so this
+            // can't fail the same way twice. Retry it so the pages
+            // after it still have an interaction to go into.
+            self.state = ParseState::Metadata;
+            let _ = metaline::parse(self, line);
+            return false;
+        }
 
-            match self.state {
-                // besides the start, a block can either be
-                // a comptime script or a message section
-                ComptimeScript(_) => self.parse_comptime(line)?,
+        true
+    }
 
-                Metadata => metaline::parse(self, line)?,
-                Message => self.parse_message(line)?,
-                Choices(ChoicesState::Choices) => endings::parse_choice(self, line)?,
-            };
+    fn parse_lines(&mut self, data: &str, recover: bool) -> (InteractionMap, Vec<ParseError>) {
+        self.pagebuf.clear();
+        self.page = Page::default();
+        self.line = 0;
+
+        let mut errors = vec![];
+        let mut skipping = false;
+
+        for (i, raw) in data.lines().enumerate() {
+            self.line = i + 1;
+            let line = raw.trim();
+            let col = raw.chars().take_while(|c| c.is_whitespace()).count() + 1;
+
+            if skipping {
+                if line == SEPARATOR {
+                    self.state = ParseState::Metadata;
+                    skipping = false;
+                    continue;
+                }
+
+                if !is_ix_header(line) {
+                    continue;
+                }
+
+                self.state = ParseState::Metadata;
+                skipping = false;
+            }
+
+            if let Err(kind) = self.parse_line(raw) {
+                errors.push(self.locate(kind, col));
+
+                if !recover {
+                    return (InteractionMap::new(), errors);
+                }
+
+                skipping = self.discard(line);
+            }
+        }
+
+        if let Err(kind) = self.push_ix() {
+            errors.push(self.locate(kind, 1));
+        }
+
+        (std::mem::take(&mut self.interactions), errors)
+    }
+
+    /// Every file read by `Import` or `Execute` directives
+    /// so far, including ones inside imported files, but not
+    /// the file being parsed itself
+    pub fn dependencies(&self) -> Vec<PathBuf> {
+        self.context.dependencies()
+    }
+
+    pub fn parse_all(&mut self, data: &str) -> std::result::Result<InteractionMap, ParseError> {
+        let (res, mut errors) = self.parse_lines(data, false);
+
+        match errors.pop() {
+            Some(e) => Err(e),
+            None => Ok(res),
         }
+    }
 
-        self.push_ix()?;
-        let res = self.interactions.clone();
-        self.interactions.clear();
-        Ok(res)
+    /// Like `parse_all`, but doesn't stop at the first error.
+    ///
+    /// After an error, the parser skips ahead to the next `---`
+    /// or `% ID` line and keeps going from there. Returns every
+    /// error it ran into, along with whatever interactions it
+    /// managed to put together.
+    ///
+    /// Errors inside an imported file only show up once, as
+    /// the error from the `Import` line.
+    pub fn parse_all_recovering(&mut self, data: &str) -> (InteractionMap, Vec<ParseError>) {
+        self.parse_lines(data, true)
     }
 }
 
+/// Whether the line starts a new interaction, ex. `% Greeting`
+fn is_ix_header(line: &str) -> bool {
+    line.split_once(char::is_whitespace)
+        .is_some_and(|(k, _)| k == "%")
+}
+
 #[cfg(test)]
 mod tests;
@@ -1,12 +1,12 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use super::*;
-use crate::comptime::ScriptError;
+use crate::comptime::ScriptErrorKind;
 use crate::pages::Metaline::*;
 use crate::pages::PageMeta;
 use crate::pages::Speaker::*;
-use crate::parser::ParseError;
-use crate::Label;
+use crate::parser::ParseErrorKind;
+use crate::{Label, MemorySource, SourceProvider};
 
 use map_macro::hash_map;
 use pretty_assertions::assert_eq;
@@ -312,19 +312,66 @@ fn parse_one_ix_many_pages() {
 #[test]
 fn page_after_end() {
     let parsed = parse_dummy_err!("vsauce");
-    assert_eq!(parsed, ParseError::PageAfterEnding);
+    assert_eq!(parsed.kind, ParseErrorKind::PageAfterEnding);
+    assert_eq!(parsed.span.line, 13);
 }
 
 #[test]
 fn double_link() {
     let parsed = parse_dummy_err!("agent_link");
-    assert_eq!(parsed, ParseError::Panic(ScriptError::DoubleLink));
+    let ParseErrorKind::Panic(e) = parsed.kind else {
+        panic!("Expected a script error, got {:?}", parsed.kind);
+    };
+
+    assert_eq!(e.kind, ScriptErrorKind::DoubleLink);
+    assert_eq!((parsed.span.line, parsed.span.col), (18, 1));
 }
 
 #[test]
 fn dupe_ix_ids() {
     let parsed = parse_dummy_err!("dupe_ix");
-    assert_eq!(parsed, ParseError::PushDuplicateIX);
+    let path = PathBuf::from(dummy_file!("dupe_ix"))
+        .canonicalize()
+        .unwrap();
+
+    assert_eq!(
+        parsed.kind,
+        ParseErrorKind::PushDuplicateIX("Lettuce".into(), path)
+    );
+    assert_eq!(parsed.span.line, 14);
+}
+
+#[test]
+fn error_span() {
+    let parsed = parse_dummy_err!("bad_meta");
+    let expected = Span::new(
+        PathBuf::from(dummy_file!("bad_meta"))
+            .canonicalize()
+            .unwrap(),
+        3,
+        5,
+    );
+
+    assert_eq!(parsed.kind, ParseErrorKind::InvalidMeta("COLOR Red".into()));
+    assert_eq!(parsed.span, expected);
+}
+
+#[test]
+fn error_span_through_import() {
+    let parsed = parse_dummy_err!("import_bad");
+    assert_eq!((parsed.span.line, parsed.span.col), (9, 1));
+
+    let ParseErrorKind::Panic(e) = parsed.kind else {
+        panic!("Expected a script error, got {:?}", parsed.kind);
+    };
+
+    let ScriptErrorKind::Import(path, inner) = e.kind else {
+        panic!("Expected an import error, got {:?}", e.kind);
+    };
+
+    assert!(path.ends_with("bad_meta.dg"));
+    assert_eq!(inner.span.path, path);
+    assert_eq!((inner.span.line, inner.span.col), (3, 5));
 }
 
 #[test]
@@ -348,7 +395,7 @@ fn newline_tricks() {
     let parsed = parse_dummy!("newlines");
     let pages = parsed.get("Newline Tricks").unwrap().pages.as_slice();
 
-    let verse = vec![
+    let verse = [
         "Buffer ended, you were not streamin',",
         "Try concat to it, try to parse through it.",
         "You wuh nah thinkin' that uh I would log to it,",
@@ -432,6 +479,28 @@ fn parse_filter_empties() {
     assert_eq!(parsed, expected);
 }
 
+#[test]
+fn markers_only_for_names() {
+    let parsed = parse_dummy!("vox_markers");
+    let pages = &parsed.get("Markers").unwrap().pages;
+
+    assert_eq!(
+        pages[0].metadata,
+        PageMeta {
+            speaker: Permanent(Narrator),
+            vox: Permanent("_".to_owned()),
+        }
+    );
+
+    assert_eq!(
+        pages[1].metadata,
+        PageMeta {
+            speaker: Permanent(Unknown),
+            vox: PageOnly("?".to_owned()),
+        }
+    );
+}
+
 #[test]
 fn parse_narrator() {
     let parsed = parse_dummy!("narrator");
@@ -494,3 +563,440 @@ Who's making me do this?"#
 
     assert_eq!(parsed, expected);
 }
+
+#[test]
+fn recover_from_errors() {
+    let data = include_str!(dummy_file!("many_errors"));
+    let (parsed, errors) = dummy_parser!("many_errors").parse_all_recovering(data);
+    let path = PathBuf::from(dummy_file!("many_errors"))
+        .canonicalize()
+        .unwrap();
+
+    let errors = errors
+        .into_iter()
+        .map(|e| (e.kind, e.span.line))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        errors,
+        vec![
+            (ParseErrorKind::InvalidMeta("COLOR Red".into()), 3),
+            (ParseErrorKind::MalformedEnding("& Not a goto".into()), 14),
+            (ParseErrorKind::PushDuplicateIX("First".into(), path), 23),
+        ]
+    );
+
+    // pages with errors are thrown out, but the rest is kept
+    assert!(parsed["First"].pages.is_empty());
+    assert!(parsed["Second"].pages.is_empty());
+    assert_eq!(parsed["Third"].pages.len(), 1);
+}
+
+#[test]
+fn recover_from_bad_import() {
+    let data = include_str!(dummy_file!("bad_import"));
+    let (_, errors) = dummy_parser!("bad_import").parse_all_recovering(data);
+
+    let [error] = errors.as_slice() else {
+        panic!("Expected exactly 1 error, got {:?}", errors);
+    };
+
+    let ParseErrorKind::Panic(ref e) = error.kind else {
+        panic!("Expected a script error, got {:?}", error.kind);
+    };
+
+    assert!(matches!(e.kind, ScriptErrorKind::NotFound(..)));
+}
+
+#[test]
+fn duplicate_keeps_imports() {
+    let data = include_str!(dummy_file!("dupe_import"));
+    let (parsed, errors) = dummy_parser!("dupe_import").parse_all_recovering(data);
+
+    let kinds = errors.into_iter().map(|e| e.kind).collect::<Vec<_>>();
+    assert!(matches!(
+        kinds.as_slice(),
+        [ParseErrorKind::PushDuplicateIX(id, _)] if id == "First"
+    ));
+
+    assert!(parsed.contains_key("First"));
+    assert!(parsed.contains_key("Test1"));
+}
+
+#[test]
+fn recover_same_as_parse_all_without_errors() {
+    let data = include_str!(dummy_file!("rodrick"));
+    let (parsed, errors) = dummy_parser!("rodrick").parse_all_recovering(data);
+
+    assert!(errors.is_empty());
+    assert_eq!(parsed, expected!(rodrick));
+}
+
+#[test]
+fn dependencies_through_imports() {
+    let data = include_str!(dummy_file!("import_sub"));
+    let mut parser = dummy_parser!("import_sub");
+    parser.parse_all(data).unwrap();
+
+    let names = parser
+        .dependencies()
+        .into_iter()
+        .map(|v| v.file_name().unwrap().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        names,
+        vec![
+            "import.dg",
+            "small_ix.dg",
+            "link.dg",
+            "two_ix.dg",
+            "one_ix_many_pages.dg",
+            "rodrick.dg",
+        ]
+    );
+}
+
+#[test]
+fn dependencies_kept_on_error() {
+    let data = include_str!(dummy_file!("import_bad"));
+    let mut parser = dummy_parser!("import_bad");
+    assert!(parser.parse_all(data).is_err());
+
+    assert_eq!(parser.dependencies().len(), 1);
+}
+
+#[test]
+fn import_cycle() {
+    let parsed = parse_dummy_err!("cycle_a");
+
+    let ParseErrorKind::Panic(e) = parsed.kind else {
+        panic!("Expected a script error, got {:?}", parsed.kind);
+    };
+
+    // the cycle gets found one import down, in cycle_b.dg
+    let ScriptErrorKind::Import(_, inner) = e.kind else {
+        panic!("Expected an import error, got {:?}", e.kind);
+    };
+
+    let ParseErrorKind::Panic(e) = inner.kind else {
+        panic!("Expected a script error, got {:?}", inner.kind);
+    };
+
+    let ScriptErrorKind::ImportCycle(cycle) = e.kind else {
+        panic!("Expected an import cycle, got {:?}", e.kind);
+    };
+
+    let names = cycle
+        .iter()
+        .map(|v| v.file_name().unwrap().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+
+    assert_eq!(names, vec!["cycle_a.dg", "cycle_b.dg", "cycle_a.dg"]);
+}
+
+#[test]
+fn import_same_file_twice() {
+    let parsed = parse_dummy!("diamond");
+    assert_eq!(parsed, expected!(import_others));
+}
+
+#[test]
+fn import_from_include_dir() {
+    let data = include_str!(dummy_file!("chapters/include_dir"));
+    let shared = PathBuf::from(dummy_file!("shared/npcs")).with_file_name("");
+
+    let parsed = dummy_parser!("chapters/include_dir")
+        .include_dirs([shared])
+        .parse_all(data)
+        .unwrap();
+
+    assert!(parsed.contains_key("Shopkeeper"));
+}
+
+#[test]
+fn import_not_found() {
+    let parsed = parse_dummy_err!("chapters/include_dir");
+
+    let ParseErrorKind::Panic(e) = parsed.kind else {
+        panic!("Expected a script error, got {:?}", parsed.kind);
+    };
+
+    let ScriptErrorKind::NotFound(path, tried) = e.kind else {
+        panic!("Expected a not found error, got {:?}", e.kind);
+    };
+
+    assert_eq!(path, PathBuf::from("npcs.dg"));
+    assert_eq!(tried.len(), 1);
+    assert!(tried[0].ends_with("chapters/npcs.dg"));
+}
+
+#[test]
+fn import_from_project_root() {
+    let data = include_str!(dummy_file!("chapters/project_root"));
+    let root = PathBuf::from(dummy_file!("small_ix")).with_file_name("");
+
+    // defaults to the entry file's folder, which is the wrong one here
+    assert!(dummy_parser!("chapters/project_root")
+        .parse_all(data)
+        .is_err());
+
+    let parsed = dummy_parser!("chapters/project_root")
+        .project_root(root)
+        .parse_all(data)
+        .unwrap();
+
+    assert!(parsed.contains_key("Shopkeeper"));
+}
+
+#[test]
+fn import_from_memory() {
+    let sources = MemorySource::new()
+        .with(
+            "game/main.dg",
+            "###\nImport chapters/one.dg\nImport npcs.dg\n###\n---\n",
+        )
+        .with(
+            "game/chapters/one.dg",
+            "###\nImport ../npcs.dg\n###\n---\n% One\nNAME Siva\n\nHi\n\n---\n",
+        )
+        .with(
+            "game/npcs.dg",
+            "% Shopkeeper\nNAME Mira\n\nWelcome in!\n\n---\n",
+        );
+
+    let mut parser = DgParser::new("game/main.dg".into()).sources(sources);
+    let data = parser.read_entry().unwrap();
+    let parsed = parser.parse_all(&data).unwrap();
+
+    let mut ids = parsed.keys().collect::<Vec<_>>();
+    ids.sort();
+    assert_eq!(ids, vec!["One", "Shopkeeper"]);
+}
+
+#[test]
+fn import_cycle_in_memory() {
+    let sources = MemorySource::new()
+        .with("a.dg", "###\nImport ./b.dg\n###\n---\n")
+        .with("b.dg", "###\nImport a.dg\n###\n---\n");
+
+    let data = sources.read(Path::new("a.dg")).unwrap();
+    let parsed = DgParser::new("a.dg".into())
+        .sources(sources)
+        .parse_all(&data)
+        .unwrap_err();
+
+    let ParseErrorKind::Panic(e) = parsed.kind else {
+        panic!("Expected a script error, got {:?}", parsed.kind);
+    };
+
+    let ScriptErrorKind::Import(_, inner) = e.kind else {
+        panic!("Expected an import error, got {:?}", e.kind);
+    };
+
+    let ParseErrorKind::Panic(e) = inner.kind else {
+        panic!("Expected a script error, got {:?}", inner.kind);
+    };
+
+    assert_eq!(
+        e.kind,
+        ScriptErrorKind::ImportCycle(vec!["a.dg".into(), "b.dg".into(), "a.dg".into()])
+    );
+}
+
+#[test]
+fn namespaced_imports() {
+    let parsed = parse_dummy!("namespaced");
+
+    let mut ids = parsed.keys().map(String::as_str).collect::<Vec<_>>();
+    ids.sort();
+    assert_eq!(
+        ids,
+        vec![
+            "A::Test1",
+            "B::Test1",
+            "Rodrick::RodrickSign",
+            "Rodrick::RodrickSign_DefNot",
+            "Rodrick::RodrickSign_Exit",
+            "Rodrick::RodrickSign_Nope",
+            "RodrickSign",
+        ]
+    );
+
+    let labels = parsed["Rodrick::RodrickSign"].ending.labels();
+    assert_eq!(
+        labels,
+        vec![
+            (Some(0), &Label::new_goto("Rodrick::RodrickSign_Nope")),
+            (Some(1), &Label::new_goto("Rodrick::RodrickSign_DefNot")),
+        ]
+    );
+
+    assert_eq!(
+        parsed["RodrickSign"].ending,
+        DialogueEnding::Label(Label::new_goto("Rodrick::RodrickSign"))
+    );
+}
+
+#[test]
+fn dupe_ix_across_imports() {
+    let sources = MemorySource::new()
+        .with("main.dg", "###\nImport a.dg\nImport b.dg\n###\n---\n")
+        .with("a.dg", "% Hi\n\nFrom a\n\n---\n")
+        .with("b.dg", "% Hi\n\nFrom b\n\n---\n");
+
+    let data = sources.read(Path::new("main.dg")).unwrap();
+    let parsed = DgParser::new("main.dg".into())
+        .sources(sources)
+        .parse_all(&data)
+        .unwrap_err();
+
+    let ParseErrorKind::Panic(e) = parsed.kind else {
+        panic!("Expected a script error, got {:?}", parsed.kind);
+    };
+
+    assert_eq!(
+        e.kind,
+        ScriptErrorKind::DuplicateIX("Hi".into(), Box::new(["a.dg".into(), "b.dg".into()]))
+    );
+    assert_eq!(e.span.line, 3);
+}
+
+#[test]
+fn dupe_ix_with_import() {
+    let sources = MemorySource::new().with("a.dg", "% Hi\n\nFrom a\n\n---\n");
+    let data = "###\nImport a.dg\n###\n---\n% Hi\n\nFrom main\n\n---\n";
+
+    let parsed = DgParser::new("main.dg".into())
+        .sources(sources)
+        .parse_all(data)
+        .unwrap_err();
+
+    assert_eq!(
+        parsed.kind,
+        ParseErrorKind::PushDuplicateIX("Hi".into(), "a.dg".into())
+    );
+    assert_eq!((parsed.span.path, parsed.span.line), ("main.dg".into(), 5));
+}
+
+#[test]
+fn invalid_namespace() {
+    let sources = MemorySource::new().with("a.dg", "% Hi\n\nFrom a\n\n---\n");
+    let data = "###\nImport a.dg as Two Words\n###\n---\n";
+
+    let parsed = DgParser::new("main.dg".into())
+        .sources(sources)
+        .parse_all(data)
+        .unwrap_err();
+
+    let ParseErrorKind::Panic(e) = parsed.kind else {
+        panic!("Expected a script error, got {:?}", parsed.kind);
+    };
+
+    assert_eq!(
+        e.kind,
+        ScriptErrorKind::InvalidNamespace("Two Words".into())
+    );
+}
+
+#[test]
+fn selective_imports() {
+    let parsed = parse_dummy!("selective");
+
+    let mut ids = parsed.keys().map(String::as_str).collect::<Vec<_>>();
+    ids.sort();
+    assert_eq!(
+        ids,
+        vec![
+            "RodrickSign_Nope",
+            "Smart::RodrickSign_DefNot",
+            "Smart::RodrickSign_Exit",
+        ]
+    );
+
+    assert_eq!(
+        parsed["Smart::RodrickSign_DefNot"].ending,
+        DialogueEnding::Label(Label::new_goto("Smart::RodrickSign_Exit"))
+    );
+
+    // not followed, so it leads nowhere now
+    assert_eq!(
+        parsed["RodrickSign_Nope"].ending,
+        DialogueEnding::Label(Label::new_goto("RodrickSign_Exit"))
+    );
+}
+
+#[test]
+fn selective_import_missing() {
+    let sources = MemorySource::new().with("a.dg", "% Hi\n\nFrom a\n\n---\n");
+    let data = "###\nImport Hi, Bye from a.dg\n###\n---\n";
+
+    let parsed = DgParser::new("main.dg".into())
+        .sources(sources)
+        .parse_all(data)
+        .unwrap_err();
+
+    let ParseErrorKind::Panic(e) = parsed.kind else {
+        panic!("Expected a script error, got {:?}", parsed.kind);
+    };
+
+    assert_eq!(e.kind, ScriptErrorKind::NoSuchInteraction("Bye".into()));
+}
+
+#[test]
+fn same_interaction_twice() {
+    let sources = MemorySource::new()
+        .with(
+            "shop.dg",
+            "% Hi\n\nWelcome\n\n---\n% Bye\n\nSee ya\n\n---\n",
+        )
+        .with("chapter.dg", "###\nImport Hi from shop.dg\n###\n---\n");
+
+    let data = "###\nImport chapter.dg\nImport shop.dg\n###\n---\n";
+    let parsed = DgParser::new("main.dg".into())
+        .sources(sources)
+        .parse_all(data)
+        .unwrap();
+
+    assert_eq!(parsed.len(), 2);
+}
+
+#[test]
+fn execute_links_apply_to_caller() {
+    let data = include_str!(dummy_file!("execute"));
+    let mut parser = dummy_parser!("execute");
+    let parsed = parser.parse_all(data).unwrap();
+
+    let expected = hash_map! {
+        "Execute Test".to_string() => Interaction {
+            pages: vec![Page {
+                metadata: PageMeta {
+                    speaker: Permanent(Named("Cherry".to_owned())),
+                    vox: Permanent("Mira".to_owned()),
+                },
+                content: "Page 1".to_owned(),
+            }],
+            ending: DialogueEnding::End,
+        }
+    };
+
+    assert_eq!(parsed, expected);
+    assert_eq!(
+        parser.context.logs(),
+        vec![
+            "Linking Cherry to Mira...",
+            "Quitting early",
+            "Done linking!",
+            "Back in execute.dg",
+        ]
+    );
+}
+
+#[test]
+fn import_already_imported_aggregate() {
+    let parsed = parse_dummy!("aggregator");
+
+    let mut expected = expected!(small_ix);
+    expected.extend(expected!(two_ix));
+    assert_eq!(parsed, expected);
+}
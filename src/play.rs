@@ -0,0 +1,293 @@
+//!
+//! `dg play`, for playtesting dialogue in the terminal
+//! without launching the whole game.
+//!
+//! Press enter to go to the next page, or type the number of
+//! a choice to pick it. `b` goes back a step, `r` restarts
+//! from the entry, and `q` quits.
+//!
+
+use std::io::{BufRead, Write};
+
+use crate::{DialogueRunner, Error, InteractionMap, ResolvedPage, RunnerError, Speaker, Step};
+
+/// Something typed in at the prompt
+enum Input {
+    Continue,
+    Choice(usize),
+    Back,
+    Restart,
+    Quit,
+}
+
+impl Input {
+    fn parse(line: &str) -> Option<Self> {
+        match line.trim() {
+            "" => Some(Self::Continue),
+            "b" | "back" => Some(Self::Back),
+            "r" | "restart" => Some(Self::Restart),
+            "q" | "quit" => Some(Self::Quit),
+
+            // choices are shown starting from 1
+            n => n.parse::<usize>().ok()?.checked_sub(1).map(Self::Choice),
+        }
+    }
+}
+
+fn write_page(out: &mut impl Write, page: &ResolvedPage) -> std::io::Result<()> {
+    match page.speaker {
+        Speaker::Narrator => writeln!(out, "{}", page.content),
+        ref speaker => writeln!(out, "{}: {}", speaker, page.content),
+    }
+}
+
+/// Read one line of input, or `Quit` at the end of input.
+/// Keeps asking until it gets something valid.
+fn prompt(input: &mut impl BufRead, out: &mut impl Write) -> Result<Input, Error> {
+    loop {
+        write!(out, "> ")?;
+        out.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            writeln!(out)?;
+            return Ok(Input::Quit);
+        }
+
+        match Input::parse(&line) {
+            Some(res) => return Ok(res),
+            None => writeln!(out, "enter, a choice number, b(ack), r(estart), or q(uit)")?,
+        }
+    }
+}
+
+/// List every interaction and ask which one to start from,
+/// by number or by ID. `None` if the user gave up.
+pub(crate) fn pick_entry(
+    map: &InteractionMap,
+    mut input: impl BufRead,
+    mut out: impl Write,
+) -> Result<Option<String>, Error> {
+    let mut ids = map.keys().collect::<Vec<_>>();
+    ids.sort();
+
+    for (i, id) in ids.iter().enumerate() {
+        writeln!(out, "  {}. {}", i + 1, id)?;
+    }
+
+    writeln!(out, "Which interaction should it start from?")?;
+
+    loop {
+        write!(out, "> ")?;
+        out.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim();
+        let picked = match line.parse::<usize>() {
+            Ok(n) => n.checked_sub(1).and_then(|i| ids.get(i)).copied(),
+            Err(_) => ids.iter().find(|id| **id == line).copied(),
+        };
+
+        match picked {
+            Some(id) => return Ok(Some(id.clone())),
+            None => writeln!(out, "no interaction {}", line)?,
+        }
+    }
+}
+
+/// Play through the dialogue starting at `entry`,
+/// reading commands from `input`
+pub fn play(
+    map: &InteractionMap,
+    entry: &str,
+    mut input: impl BufRead,
+    mut out: impl Write,
+) -> Result<(), Error> {
+    let start = DialogueRunner::new(map, entry)?;
+    let mut runner = start.clone();
+
+    // the runner from before each step, for going back
+    let mut history: Vec<DialogueRunner> = vec![];
+    let mut last_id = None;
+
+    loop {
+        let before = runner.clone();
+
+        // probably a goto that leads nowhere, but going back
+        // or restarting should still work after one of those
+        let step = match runner.advance() {
+            Ok(step) => Some(step),
+            Err(e) => {
+                writeln!(out, "error: {}", e)?;
+                None
+            }
+        };
+
+        if last_id.as_deref() != Some(runner.interaction_id()) {
+            writeln!(out, "\n[{}]", runner.interaction_id())?;
+            last_id = Some(runner.interaction_id().to_owned());
+        }
+
+        match step {
+            Some(Step::Page(ref page)) => write_page(&mut out, page)?,
+
+            Some(Step::Choices(choices)) => {
+                for (i, choice) in choices.iter().enumerate() {
+                    writeln!(out, "  {}. {}", i + 1, choice.text)?;
+                }
+            }
+
+            Some(Step::End) => writeln!(out, "(end)")?,
+            None => {}
+        }
+
+        let cmd = loop {
+            match prompt(&mut input, &mut out)? {
+                Input::Choice(i) if runner.is_choosing() => match runner.choose(i) {
+                    Ok(()) => break Input::Continue,
+
+                    Err(RunnerError::InvalidChoice(_, len)) => {
+                        writeln!(out, "pick a number from 1 to {}", len)?;
+                    }
+
+                    // probably a goto that leads nowhere
+                    Err(e) => writeln!(out, "error: {}", e)?,
+                },
+
+                Input::Choice(_) => writeln!(out, "there's nothing to choose right now")?,
+
+                // can't skip past choices without picking one
+                Input::Continue if runner.is_choosing() => {
+                    writeln!(out, "pick a choice first")?;
+                }
+
+                Input::Continue if step.is_none() => {
+                    writeln!(out, "can't go any further, try b(ack) or r(estart)")?;
+                }
+
+                cmd => break cmd,
+            }
+        };
+
+        match cmd {
+            Input::Continue if step == Some(Step::End) => return Ok(()),
+            Input::Continue | Input::Choice(_) => history.push(before),
+
+            Input::Back => {
+                runner = history.pop().unwrap_or_else(|| start.clone());
+                last_id = None;
+            }
+
+            Input::Restart => {
+                writeln!(out, "(restarting)")?;
+                runner = start.clone();
+                history.clear();
+                last_id = None;
+            }
+
+            Input::Quit => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::parse_dummy;
+
+    use pretty_assertions::assert_eq;
+
+    fn play_with(name: &str, entry: &str, input: &str) -> String {
+        let mut out = vec![];
+        play(&parse_dummy(name), entry, input.as_bytes(), &mut out).unwrap();
+
+        // trailing spaces are easy to lose in the expected strings below
+        String::from_utf8(out).unwrap().replace("> \n", ">\n")
+    }
+
+    #[test]
+    fn play_through() {
+        let out = play_with("rodrick.dg", "RodrickSign", "\n\n\n7\n2\n\n\n\n");
+        let expected = "
+[RodrickSign]
+Rodrick Sign Co.: So... you're reading a sign, eh?
+> Rodrick Sign Co.: Well...
+> Rodrick Sign Co.: Are you smart?
+>   1. Nope
+  2. Definitely not
+> pick a number from 1 to 2
+>
+[RodrickSign_DefNot]
+Rodrick Sign Co.: Yeah, I definitely didn't think so.
+>
+[RodrickSign_Exit]
+Rodrick Sign Co.: Come back when you're smart.
+> (end)
+> ";
+
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn back_and_restart() {
+        let out = play_with("rodrick.dg", "RodrickSign", "\nb\nr\nq\n");
+        let expected = "
+[RodrickSign]
+Rodrick Sign Co.: So... you're reading a sign, eh?
+> Rodrick Sign Co.: Well...
+>
+[RodrickSign]
+Rodrick Sign Co.: So... you're reading a sign, eh?
+> (restarting)
+
+[RodrickSign]
+Rodrick Sign Co.: So... you're reading a sign, eh?
+> ";
+
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn back_across_choices() {
+        let out = play_with("reachability.dg", "Start", "\n1\nb\nb\n\n3\n\n");
+        assert!(out.ends_with("  3. Nowhere\n> (end)\n> "));
+        assert_eq!(out.matches("Round and round...").count(), 1);
+        assert_eq!(out.matches("Where to?").count(), 2);
+    }
+
+    #[test]
+    fn dangling_goto() {
+        let out = play_with("dangling.dg", "Shop_Buy", "\n\nb\nq\n");
+        let expected = "
+[Shop_Buy]
+Shopkeeper: Nothing's for sale yet.
+> error: No interaction with ID Shop_Exit
+> can't go any further, try b(ack) or r(estart)
+>
+[Shop_Buy]
+Shopkeeper: Nothing's for sale yet.
+> ";
+
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn pick_by_number_or_id() {
+        let map = parse_dummy("rodrick.dg");
+        let pick = |input: &str| pick_entry(&map, input.as_bytes(), vec![]).unwrap();
+
+        assert_eq!(pick("2\n").as_deref(), Some("RodrickSign_DefNot"));
+        assert_eq!(pick("9\nRodrickSign\n").as_deref(), Some("RodrickSign"));
+        assert_eq!(pick("nope\n"), None);
+    }
+
+    #[test]
+    fn stops_at_end_of_input() {
+        let out = play_with("rodrick.dg", "RodrickSign", "\n");
+        assert!(out.ends_with("Well...\n>\n"));
+    }
+}
@@ -0,0 +1,263 @@
+//!
+//! Finding dialogue that players can never see, and
+//! dialogue that players can never get out of
+//!
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{DialogueEnding, InteractionMap, Label};
+
+/// Results of `analyze_reachability`. Everything is sorted,
+/// so the output is stable between runs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Reachability {
+    /// Entry IDs that aren't interactions
+    pub unknown_entries: Vec<String>,
+
+    /// Interactions that can't be reached from any entry
+    pub unreachable: Vec<String>,
+
+    /// Groups of reachable interactions that only ever go
+    /// to each other, with no way for the dialogue to end
+    pub endless_cycles: Vec<Vec<String>>,
+
+    /// Reachable interactions with choices where every
+    /// branch eventually comes back to the same interaction
+    pub looping_choices: Vec<String>,
+}
+
+impl Reachability {
+    /// `true` if nothing suspicious was found
+    pub fn is_clean(&self) -> bool {
+        self.unknown_entries.is_empty()
+            && self.unreachable.is_empty()
+            && self.endless_cycles.is_empty()
+            && self.looping_choices.is_empty()
+    }
+}
+
+/// Interaction IDs, and the IDs each one can go to next.
+/// Gotos to interactions that don't exist are left out,
+/// since `check_gotos` already reports those.
+type Graph<'a> = HashMap<&'a str, Vec<&'a str>>;
+
+fn build_graph(map: &InteractionMap) -> Graph<'_> {
+    map.iter()
+        .map(|(id, ix)| {
+            let targets = ix
+                .ending
+                .labels()
+                .into_iter()
+                .map(|(_, Label::Goto(target))| target.as_str())
+                .filter(|target| map.contains_key(*target))
+                .collect();
+
+            (id.as_str(), targets)
+        })
+        .collect()
+}
+
+/// Whether the dialogue can stop at this interaction,
+/// either by ending or by picking a choice with no goto
+fn can_end(ending: &DialogueEnding) -> bool {
+    match ending {
+        DialogueEnding::End => true,
+        DialogueEnding::Label(_) => false,
+        DialogueEnding::Choices(choices) => choices.iter().any(|c| c.label.is_none()),
+    }
+}
+
+/// Tarjan's algorithm... returns a map of each ID to the
+/// index of the strongly connected component it's in,
+/// along with the list of components.
+fn components<'a>(graph: &Graph<'a>) -> (HashMap<&'a str, usize>, Vec<Vec<&'a str>>) {
+    struct Tarjan<'a, 'g> {
+        graph: &'g Graph<'a>,
+        index: HashMap<&'a str, usize>,
+        lowlink: HashMap<&'a str, usize>,
+        stack: Vec<&'a str>,
+        on_stack: HashSet<&'a str>,
+        comp_of: HashMap<&'a str, usize>,
+        comps: Vec<Vec<&'a str>>,
+    }
+
+    impl<'a> Tarjan<'a, '_> {
+        fn visit(&mut self, node: &'a str) {
+            let i = self.index.len();
+            self.index.insert(node, i);
+            self.lowlink.insert(node, i);
+            self.stack.push(node);
+            self.on_stack.insert(node);
+
+            for &next in &self.graph[node] {
+                if !self.index.contains_key(next) {
+                    self.visit(next);
+                    let low = self.lowlink[node].min(self.lowlink[next]);
+                    self.lowlink.insert(node, low);
+                } else if self.on_stack.contains(next) {
+                    let low = self.lowlink[node].min(self.index[next]);
+                    self.lowlink.insert(node, low);
+                }
+            }
+
+            if self.lowlink[node] != self.index[node] {
+                return;
+            }
+
+            let mut comp = vec![];
+            while let Some(member) = self.stack.pop() {
+                self.on_stack.remove(member);
+                self.comp_of.insert(member, self.comps.len());
+                comp.push(member);
+
+                if member == node {
+                    break;
+                }
+            }
+
+            self.comps.push(comp);
+        }
+    }
+
+    let mut tarjan = Tarjan {
+        graph,
+        index: HashMap::new(),
+        lowlink: HashMap::new(),
+        stack: vec![],
+        on_stack: HashSet::new(),
+        comp_of: HashMap::new(),
+        comps: vec![],
+    };
+
+    let mut ids = graph.keys().copied().collect::<Vec<_>>();
+    ids.sort();
+
+    for id in ids {
+        if !tarjan.index.contains_key(id) {
+            tarjan.visit(id);
+        }
+    }
+
+    (tarjan.comp_of, tarjan.comps)
+}
+
+/// Every interaction reachable by following gotos from `entries`
+fn reachable_from<'a>(graph: &Graph<'a>, entries: &[&'a str]) -> HashSet<&'a str> {
+    let mut seen = HashSet::new();
+    let mut todo = entries.to_vec();
+
+    while let Some(id) = todo.pop() {
+        if seen.insert(id) {
+            todo.extend(&graph[id]);
+        }
+    }
+
+    seen
+}
+
+/// Starting from the interactions in `entries`, find dialogue
+/// that can never be reached, cycles the dialogue can never
+/// leave, and choices that all loop back to where they started.
+pub fn analyze_reachability<S: AsRef<str>>(map: &InteractionMap, entries: &[S]) -> Reachability {
+    let graph = build_graph(map);
+    let mut res = Reachability::default();
+
+    let (known, unknown): (Vec<_>, Vec<_>) = entries
+        .iter()
+        .map(AsRef::as_ref)
+        .partition(|id| map.contains_key(*id));
+
+    res.unknown_entries = unknown.into_iter().map(str::to_owned).collect();
+    res.unknown_entries.sort();
+
+    let known = known
+        .into_iter()
+        .filter_map(|id| map.get_key_value(id).map(|(k, _)| k.as_str()))
+        .collect::<Vec<_>>();
+
+    let reachable = reachable_from(&graph, &known);
+    res.unreachable = map
+        .keys()
+        .filter(|id| !reachable.contains(id.as_str()))
+        .cloned()
+        .collect();
+    res.unreachable.sort();
+
+    let (comp_of, comps) = components(&graph);
+
+    for comp in &comps {
+        let is_cycle = comp.len() > 1 || graph[comp[0]].contains(&comp[0]);
+        if !is_cycle || !reachable.contains(comp[0]) {
+            continue;
+        }
+
+        let has_exit = comp.iter().any(|id| {
+            can_end(&map[*id].ending) || graph[id].iter().any(|next| comp_of[next] != comp_of[id])
+        });
+
+        if !has_exit {
+            let mut ids = comp.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+            ids.sort();
+            res.endless_cycles.push(ids);
+        }
+    }
+
+    res.endless_cycles.sort();
+
+    for id in &reachable {
+        let DialogueEnding::Choices(ref choices) = map[*id].ending else {
+            continue;
+        };
+
+        let loops_back = !choices.is_empty()
+            && choices.iter().all(|choice| match choice.label {
+                Some(Label::Goto(ref target)) => comp_of.get(target.as_str()) == Some(&comp_of[id]),
+                None => false,
+            });
+
+        if loops_back {
+            res.looping_choices.push(id.to_string());
+        }
+    }
+
+    res.looping_choices.sort();
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::parse_dummy;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn rodrick_is_clean() {
+        let res = analyze_reachability(&parse_dummy("rodrick.dg"), &["RodrickSign"]);
+        assert!(res.is_clean(), "{:?}", res);
+    }
+
+    #[test]
+    fn find_everything() {
+        let map = parse_dummy("reachability.dg");
+        let res = analyze_reachability(&map, &["Start", "Nonexistent"]);
+
+        let expected = Reachability {
+            unknown_entries: vec!["Nonexistent".into()],
+            unreachable: vec!["Orphan".into()],
+            endless_cycles: vec![vec!["Loop_A".into(), "Loop_B".into()]],
+            looping_choices: vec!["Hub".into()],
+        };
+
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn no_entries() {
+        let map = parse_dummy("reachability.dg");
+        let res = analyze_reachability::<&str>(&map, &[]);
+
+        assert_eq!(res.unreachable.len(), map.len());
+        assert!(res.endless_cycles.is_empty());
+    }
+}
@@ -0,0 +1,324 @@
+//!
+//! Walking through compiled interactions at runtime, so game
+//! code doesn't have to figure out what comes after what.
+//!
+//! ```no_run
+//! # use dialogical::{DialogueRunner, InteractionMap, Step};
+//! # let map = InteractionMap::new();
+//! let mut runner = DialogueRunner::new(&map, "Greeting").unwrap();
+//!
+//! loop {
+//!     match runner.advance().unwrap() {
+//!         Step::Page(page) => println!("{}: {}", page.speaker, page.content),
+//!         Step::Choices(_) => runner.choose(0).unwrap(),
+//!         Step::End => break,
+//!     }
+//! }
+//! ```
+//!
+
+use std::collections::HashSet;
+
+use thiserror::Error;
+
+use crate::{DialogueChoice, DialogueEnding, Interaction, InteractionMap, Label, ResolvedPage};
+
+/// What the runner has to show next
+#[derive(Clone, Debug, PartialEq)]
+pub enum Step<'a> {
+    Page(ResolvedPage),
+
+    /// Waiting on `DialogueRunner::choose`
+    Choices(&'a [DialogueChoice]),
+
+    /// The dialogue is over
+    End,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum RunnerError {
+    #[error("No interaction with ID {0}")]
+    NoSuchInteraction(String),
+
+    #[error("Not waiting on a choice right now")]
+    NotChoosing,
+
+    #[error("Picked choice {0}, but there are only {1}")]
+    InvalidChoice(usize, usize),
+
+    #[error("Gotos starting at {0} loop back around without any pages in between")]
+    EmptyGotoCycle(String),
+}
+
+/// Plays through interactions one page at a time, following
+/// gotos and choices until the dialogue ends.
+#[derive(Clone, Debug)]
+pub struct DialogueRunner<'a> {
+    map: &'a InteractionMap,
+
+    id: String,
+    ix: &'a Interaction,
+    pages: Vec<ResolvedPage>,
+
+    /// Index of the next page to show
+    page: usize,
+
+    choosing: bool,
+    ended: bool,
+}
+
+impl<'a> DialogueRunner<'a> {
+    /// Start the dialogue at the interaction with ID `start`
+    pub fn new(map: &'a InteractionMap, start: &str) -> Result<Self, RunnerError> {
+        let ix = map
+            .get(start)
+            .ok_or_else(|| RunnerError::NoSuchInteraction(start.to_owned()))?;
+
+        Ok(Self {
+            map,
+            id: start.to_owned(),
+            ix,
+            pages: ix.resolved_pages(),
+            page: 0,
+            choosing: false,
+            ended: false,
+        })
+    }
+
+    /// ID of the interaction the runner is in
+    pub fn interaction_id(&self) -> &str {
+        &self.id
+    }
+
+    /// Whether the runner is waiting on `choose`
+    pub fn is_choosing(&self) -> bool {
+        self.choosing
+    }
+
+    pub fn is_ended(&self) -> bool {
+        self.ended
+    }
+
+    /// Jump to the start of another interaction.
+    ///
+    /// Speaker and vox don't carry over, since the same
+    /// interaction can be reached from anywhere.
+    fn goto(&mut self, id: &str) -> Result<(), RunnerError> {
+        *self = Self::new(self.map, id)?;
+        Ok(())
+    }
+
+    /// Move on to whatever comes next.
+    ///
+    /// While waiting on a choice, this keeps giving back the
+    /// same choices, and once the dialogue ends, it keeps
+    /// giving back `Step::End`.
+    pub fn advance(&mut self) -> Result<Step<'a>, RunnerError> {
+        // interactions left through a goto since the last page,
+        // so ones without pages can't send us around forever
+        let mut visited = HashSet::new();
+
+        loop {
+            if self.ended {
+                return Ok(Step::End);
+            }
+
+            if let Some(page) = self.pages.get(self.page) {
+                self.page += 1;
+                return Ok(Step::Page(page.clone()));
+            }
+
+            match self.ix.ending {
+                DialogueEnding::End => {
+                    self.ended = true;
+                    return Ok(Step::End);
+                }
+
+                DialogueEnding::Label(Label::Goto(ref id)) => {
+                    if !visited.insert(self.id.clone()) {
+                        return Err(RunnerError::EmptyGotoCycle(self.id.clone()));
+                    }
+
+                    self.goto(id)?;
+                }
+
+                DialogueEnding::Choices(ref choices) => {
+                    self.choosing = true;
+                    return Ok(Step::Choices(choices));
+                }
+            }
+        }
+    }
+
+    /// Pick one of the choices from the last `Step::Choices`.
+    ///
+    /// Choices without a goto end the dialogue.
+    pub fn choose(&mut self, index: usize) -> Result<(), RunnerError> {
+        let DialogueEnding::Choices(ref choices) = self.ix.ending else {
+            return Err(RunnerError::NotChoosing);
+        };
+
+        if !self.choosing {
+            return Err(RunnerError::NotChoosing);
+        }
+
+        let choice = choices
+            .get(index)
+            .ok_or(RunnerError::InvalidChoice(index, choices.len()))?;
+
+        match choice.label {
+            Some(Label::Goto(ref id)) => self.goto(id),
+
+            None => {
+                self.choosing = false;
+                self.ended = true;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::parse_dummy;
+    use crate::Speaker;
+
+    use pretty_assertions::assert_eq;
+
+    fn page(speaker: &str, vox: &str, content: &str) -> Step<'static> {
+        Step::Page(ResolvedPage {
+            speaker: Speaker::Named(speaker.to_owned()),
+            vox: vox.to_owned(),
+            content: content.to_owned(),
+        })
+    }
+
+    #[test]
+    fn pageonly_metadata() {
+        let map = parse_dummy("pageonly.dg");
+        let mut runner = DialogueRunner::new(&map, "PageOnly Test").unwrap();
+
+        assert_eq!(runner.advance(), Ok(page("Mira", "Mira", "What's up?")));
+        assert_eq!(
+            runner.advance(),
+            Ok(page("Mira", "Ethan", "Nothing much..."))
+        );
+        assert_eq!(
+            runner.advance(),
+            Ok(page(
+                "Mira",
+                "Mira",
+                "Alright, why am I talking to myself?\nWho's making me do this?"
+            ))
+        );
+
+        assert_eq!(runner.advance(), Ok(Step::End));
+        assert_eq!(runner.advance(), Ok(Step::End));
+        assert!(runner.is_ended());
+    }
+
+    #[test]
+    fn choices_and_gotos() {
+        let map = parse_dummy("rodrick.dg");
+        let mut runner = DialogueRunner::new(&map, "RodrickSign").unwrap();
+        let speaker = "Rodrick Sign Co.";
+
+        for content in [
+            "So... you're reading a sign, eh?",
+            "Well...",
+            "Are you smart?",
+        ] {
+            assert_eq!(runner.advance(), Ok(page(speaker, "Default", content)));
+        }
+
+        let Ok(Step::Choices(choices)) = runner.advance() else {
+            panic!("expected choices");
+        };
+
+        assert_eq!(choices.len(), 2);
+        assert_eq!(runner.choose(2), Err(RunnerError::InvalidChoice(2, 2)));
+        runner.choose(1).unwrap();
+
+        assert_eq!(runner.interaction_id(), "RodrickSign_DefNot");
+        assert_eq!(
+            runner.advance(),
+            Ok(page(
+                speaker,
+                "Default",
+                "Yeah, I definitely didn't think so."
+            ))
+        );
+
+        // follows the goto without stopping
+        assert_eq!(
+            runner.advance(),
+            Ok(page(speaker, "Default", "Come back when you're smart."))
+        );
+        assert_eq!(runner.interaction_id(), "RodrickSign_Exit");
+        assert_eq!(runner.advance(), Ok(Step::End));
+    }
+
+    #[test]
+    fn choice_without_goto_ends() {
+        let map = parse_dummy("reachability.dg");
+        let mut runner = DialogueRunner::new(&map, "Start").unwrap();
+
+        assert_eq!(runner.choose(0), Err(RunnerError::NotChoosing));
+
+        runner.advance().unwrap();
+        assert!(matches!(runner.advance(), Ok(Step::Choices(_))));
+        assert!(runner.is_choosing());
+
+        runner.choose(2).unwrap();
+        assert_eq!(runner.advance(), Ok(Step::End));
+    }
+
+    #[test]
+    fn missing_interactions() {
+        let map = parse_dummy("dangling.dg");
+
+        let err = DialogueRunner::new(&map, "Nope").unwrap_err();
+        assert_eq!(err, RunnerError::NoSuchInteraction("Nope".to_owned()));
+
+        let mut runner = DialogueRunner::new(&map, "Shop").unwrap();
+        runner.advance().unwrap();
+        runner.advance().unwrap();
+        runner.advance().unwrap();
+
+        let err = runner.choose(1).unwrap_err();
+        assert_eq!(err, RunnerError::NoSuchInteraction("Shop_Sell".to_owned()));
+    }
+
+    #[test]
+    fn empty_goto_cycle() {
+        let goto = |id: &str| Interaction {
+            pages: vec![],
+            ending: DialogueEnding::Label(Label::Goto(id.to_owned())),
+        };
+
+        let mut map = parse_dummy("two_ix.dg");
+        map.get_mut("Second").unwrap().ending = DialogueEnding::Label(Label::Goto("A".to_owned()));
+        map.insert("A".to_owned(), goto("B"));
+        map.insert("B".to_owned(), goto("A"));
+
+        // shows all the pages before noticing the loop
+        let mut runner = DialogueRunner::new(&map, "Second").unwrap();
+        runner.advance().unwrap();
+        runner.advance().unwrap();
+        assert_eq!(
+            runner.advance(),
+            Err(RunnerError::EmptyGotoCycle("A".to_owned()))
+        );
+
+        // but loops that do have pages in them are fine
+        map.get_mut("B").unwrap().ending = DialogueEnding::Label(Label::Goto("Second".to_owned()));
+        let mut runner = DialogueRunner::new(&map, "Second").unwrap();
+
+        for _ in 0..3 {
+            runner.advance().unwrap();
+            runner.advance().unwrap();
+            assert_eq!(runner.interaction_id(), "Second");
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//!
+//! Where the parser gets the files it `Import`s and `Execute`s
+//! from. Usually that's the filesystem, but editors with unsaved
+//! buffers (or WASM, or tests that don't want temp files) can
+//! hand it files straight from memory instead.
+//!
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+pub trait SourceProvider: Send + Sync {
+    /// Contents of the file at `path`
+    fn read(&self, path: &Path) -> io::Result<String>;
+
+    /// Whether there's a file at `path`, for searching
+    /// through include dirs
+    fn exists(&self, path: &Path) -> bool;
+
+    /// The same path no matter how the file was reached, for
+    /// spotting files that get imported more than once
+    fn canonicalize(&self, path: &Path) -> PathBuf {
+        normalize(path)
+    }
+}
+
+/// Reads files off the disk, like you'd expect
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FsSource;
+
+impl SourceProvider for FsSource {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    /// Resolves symlinks too, but falls back to the path
+    /// as-is if it doesn't exist (yet)
+    fn canonicalize(&self, path: &Path) -> PathBuf {
+        path.canonicalize().unwrap_or_else(|_| path.to_owned())
+    }
+}
+
+/// Files kept in a map instead of on the disk. Paths are
+/// compared after getting rid of any `.` and `..` in them.
+#[derive(Clone, Debug, Default)]
+pub struct MemorySource {
+    files: HashMap<PathBuf, String>,
+}
+
+impl MemorySource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file, replacing it if it's already there
+    pub fn insert(&mut self, path: impl AsRef<Path>, contents: impl Into<String>) {
+        self.files.insert(normalize(path.as_ref()), contents.into());
+    }
+
+    /// Same as `insert`, but for building one up all at once
+    pub fn with(mut self, path: impl AsRef<Path>, contents: impl Into<String>) -> Self {
+        self.insert(path, contents);
+        self
+    }
+
+    pub fn remove(&mut self, path: impl AsRef<Path>) -> Option<String> {
+        self.files.remove(&normalize(path.as_ref()))
+    }
+}
+
+impl SourceProvider for MemorySource {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.files
+            .get(&normalize(path))
+            .cloned()
+            .ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files.contains_key(&normalize(path))
+    }
+}
+
+/// Get rid of `.` and `..` without touching the disk
+fn normalize(path: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+
+    for part in path.components() {
+        match part {
+            Component::CurDir => {}
+
+            // only go up if there's a folder name to undo,
+            // so stuff like `../../a.dg` stays as it is
+            Component::ParentDir
+                if matches!(res.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                res.pop();
+            }
+
+            part => res.push(part),
+        }
+    }
+
+    res
+}
+
+/// The provider a parser and everything it imports share
+#[derive(Clone)]
+pub(crate) struct Sources(pub Arc<dyn SourceProvider>);
+
+impl Default for Sources {
+    fn default() -> Self {
+        Self(Arc::new(FsSource))
+    }
+}
+
+impl fmt::Debug for Sources {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Sources(..)")
+    }
+}
+
+impl PartialEq for Sources {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl std::ops::Deref for Sources {
+    type Target = dyn SourceProvider;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn normalize_paths() {
+        assert_eq!(normalize(Path::new("a/./b/../c.dg")), Path::new("a/c.dg"));
+        assert_eq!(normalize(Path::new("/a/../b.dg")), Path::new("/b.dg"));
+        assert_eq!(normalize(Path::new("../../a.dg")), Path::new("../../a.dg"));
+    }
+
+    #[test]
+    fn memory_source() {
+        let mut src = MemorySource::new().with("chapters/one.dg", "hi");
+        src.insert("chapters/two.dg", "hello");
+
+        assert!(src.exists(Path::new("chapters/../chapters/one.dg")));
+        assert_eq!(src.read(Path::new("./chapters/two.dg")).unwrap(), "hello");
+        assert_eq!(src.remove("chapters/one.dg").as_deref(), Some("hi"));
+        assert!(!src.exists(Path::new("chapters/one.dg")));
+    }
+}
@@ -0,0 +1,21 @@
+//!
+//! Fixture helpers shared by the unit tests
+//!
+
+use std::path::PathBuf;
+
+use crate::{DgParser, InteractionMap};
+
+/// Path to a file in `dummy_data`
+pub(crate) fn dummy_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("dummy_data")
+        .join(name)
+}
+
+/// Parse a file in `dummy_data`, which had better not have errors
+pub(crate) fn parse_dummy(name: &str) -> InteractionMap {
+    let path = dummy_path(name);
+    let data = std::fs::read_to_string(&path).unwrap();
+    DgParser::new(path).parse_all(&data).unwrap()
+}
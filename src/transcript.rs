@@ -0,0 +1,302 @@
+//!
+//! Transcript tests, for catching changes to how dialogue
+//! plays out. A transcript file looks like this:
+//!
+//! ```text
+//! // comments and empty lines are ignored
+//! % RodrickSign
+//! Rodrick Sign Co.: So... you're reading a sign, eh?
+//! Rodrick Sign Co.: Are you smart?
+//! > 2
+//! Rodrick Sign Co.: Yeah, I definitely didn't think so.
+//! ```
+//!
+//! Each `% ID` starts a new test from that interaction. Pages
+//! are `Speaker: text`, with `_` for the narrator, `?` for an
+//! unknown speaker, and `\n` for newlines. `> N` picks choice
+//! number N, counting from 1 like `dg play` does. The test
+//! passes if the dialogue plays out exactly like that, all
+//! the way to the end.
+//!
+
+use std::fmt::Write;
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::{DialogueRunner, InteractionMap, ResolvedPage, Span, Speaker, Step};
+
+/// One test in a transcript file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transcript {
+    /// ID of the interaction it starts from
+    pub entry: String,
+
+    /// Where the `% ID` line is
+    pub span: Span,
+
+    /// Every line after it, trimmed
+    pub lines: Vec<String>,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum TranscriptError {
+    #[error("{0}: expected a `% ID` line before this")]
+    NoEntry(Span),
+
+    #[error("{0}: {1} is not a choice number")]
+    BadChoice(Span, String),
+}
+
+/// Split a transcript file into its tests
+pub fn parse_transcripts(data: &str, path: &Path) -> Result<Vec<Transcript>, TranscriptError> {
+    let mut res: Vec<Transcript> = vec![];
+
+    for (i, line) in data.lines().enumerate() {
+        let line = line.trim();
+        let span = Span::new(path.to_owned(), i + 1, 1);
+
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        if let Some(entry) = line.strip_prefix("% ") {
+            res.push(Transcript {
+                entry: entry.trim().to_owned(),
+                span,
+                lines: vec![],
+            });
+
+            continue;
+        }
+
+        // written the same way as the actual output, so `>1`
+        // and `>  1` still match `> 1`
+        let line = match line.strip_prefix('>') {
+            Some(n) => match n.trim().parse::<usize>() {
+                Ok(n) if n > 0 => format!("> {}", n),
+                _ => return Err(TranscriptError::BadChoice(span, n.trim().to_owned())),
+            },
+
+            None => line.to_owned(),
+        };
+
+        let Some(test) = res.last_mut() else {
+            return Err(TranscriptError::NoEntry(span));
+        };
+
+        test.lines.push(line);
+    }
+
+    Ok(res)
+}
+
+/// A page the way it's written in transcripts
+fn page_line(page: &ResolvedPage) -> String {
+    let speaker = match page.speaker {
+        Speaker::Named(ref name) => name,
+        Speaker::Narrator => "_",
+        Speaker::Unknown => "?",
+    };
+
+    format!("{}: {}", speaker, page.content.replace('\n', "\\n"))
+}
+
+/// Play through the dialogue, picking choices from the
+/// transcript, and write down what happens. Stops early
+/// once it's clearly not going to match.
+fn play_transcript(map: &InteractionMap, test: &Transcript) -> Vec<String> {
+    let mut runner = match DialogueRunner::new(map, &test.entry) {
+        Ok(v) => v,
+        Err(e) => return vec![format!("(error: {})", e)],
+    };
+
+    let mut choices = test
+        .lines
+        .iter()
+        .filter_map(|v| v.strip_prefix('>'))
+        .map(|v| v.trim().parse::<usize>().unwrap_or_default());
+
+    let mut res = vec![];
+
+    // gotos that go in circles would never end otherwise
+    while res.len() <= test.lines.len() {
+        match runner.advance() {
+            Ok(Step::Page(page)) => res.push(page_line(&page)),
+            Ok(Step::End) => break,
+
+            Ok(Step::Choices(_)) => {
+                let Some(n) = choices.next() else {
+                    res.push("(waiting on a choice)".to_owned());
+                    break;
+                };
+
+                res.push(format!("> {}", n));
+
+                if let Err(e) = runner.choose(n.saturating_sub(1)) {
+                    res.push(format!("(error: {})", e));
+                    break;
+                }
+            }
+
+            Err(e) => {
+                res.push(format!("(error: {})", e));
+                break;
+            }
+        }
+    }
+
+    res
+}
+
+/// Line-by-line diff, with `-` for lines only in `expected`
+/// and `+` for lines only in `actual`
+fn diff(expected: &[String], actual: &[String]) -> String {
+    // longest common subsequence, from the end
+    let mut lcs = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lcs[i][j] = match expected[i] == actual[j] {
+                true => lcs[i + 1][j + 1] + 1,
+                false => lcs[i + 1][j].max(lcs[i][j + 1]),
+            };
+        }
+    }
+
+    let mut res = String::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            let _ = writeln!(res, "  {}", expected[i]);
+            i += 1;
+            j += 1;
+        } else if i < expected.len() && (j == actual.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            let _ = writeln!(res, "- {}", expected[i]);
+            i += 1;
+        } else {
+            let _ = writeln!(res, "+ {}", actual[j]);
+            j += 1;
+        }
+    }
+
+    res
+}
+
+/// Run one transcript test. On failure, gives back a diff of
+/// what was expected against what actually happened.
+pub fn run_transcript(map: &InteractionMap, test: &Transcript) -> Result<(), String> {
+    let actual = play_transcript(map, test);
+
+    match actual == test.lines {
+        true => Ok(()),
+        false => Err(diff(&test.lines, &actual)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{dummy_path, parse_dummy};
+
+    use pretty_assertions::assert_eq;
+
+    fn dummy_transcripts(name: &str) -> Vec<Transcript> {
+        let path = dummy_path(name);
+        parse_transcripts(&std::fs::read_to_string(&path).unwrap(), &path).unwrap()
+    }
+
+    #[test]
+    fn passing_transcripts() {
+        let map = parse_dummy("rodrick.dg");
+        let tests = dummy_transcripts("rodrick.dgt");
+
+        assert_eq!(tests.len(), 3);
+        for test in &tests {
+            assert_eq!(run_transcript(&map, test), Ok(()), "{}", test.entry);
+        }
+    }
+
+    #[test]
+    fn failing_transcript_diff() {
+        let map = parse_dummy("rodrick.dg");
+        let test = &parse_transcripts(
+            "% RodrickSign_Nope\n\
+            Rodrick Sign Co.: Yeah, I didn't think so!\n\
+            Rodrick Sign Co.: Come back when you're smart.\n\
+            Rodrick Sign Co.: Bye!\n",
+            Path::new("test.dgt"),
+        )
+        .unwrap()[0];
+
+        let expected = "\
+            - Rodrick Sign Co.: Yeah, I didn't think so!\n\
+            + Rodrick Sign Co.: Yeah, I didn't think so.\n  \
+            Rodrick Sign Co.: Come back when you're smart.\n\
+            - Rodrick Sign Co.: Bye!\n";
+
+        assert_eq!(run_transcript(&map, test), Err(expected.to_owned()));
+    }
+
+    #[test]
+    fn missing_choice() {
+        let map = parse_dummy("reachability.dg");
+        let test =
+            &parse_transcripts("% Start\nSiva: Where to?\n", Path::new("test.dgt")).unwrap()[0];
+
+        assert_eq!(
+            play_transcript(&map, test),
+            ["Siva: Where to?", "(waiting on a choice)"]
+        );
+    }
+
+    #[test]
+    fn endless_loop_stops() {
+        let map = parse_dummy("reachability.dg");
+        let test = &parse_transcripts(
+            "% Loop_A\nSiva: Round and round...\n",
+            Path::new("test.dgt"),
+        )
+        .unwrap()[0];
+
+        assert_eq!(play_transcript(&map, test).len(), 2);
+        assert!(run_transcript(&map, test).is_err());
+    }
+
+    #[test]
+    fn choice_spacing() {
+        let map = parse_dummy("rodrick.dg");
+        let test = &parse_transcripts(
+            "% RodrickSign\n\
+            Rodrick Sign Co.: So... you're reading a sign, eh?\n\
+            Rodrick Sign Co.: Well...\n\
+            Rodrick Sign Co.: Are you smart?\n\
+            >1\n\
+            Rodrick Sign Co.: Yeah, I didn't think so.\n\
+            Rodrick Sign Co.: Come back when you're smart.\n",
+            Path::new("test.dgt"),
+        )
+        .unwrap()[0];
+
+        assert_eq!(test.lines[3], "> 1");
+        assert_eq!(run_transcript(&map, test), Ok(()));
+    }
+
+    #[test]
+    fn bad_transcripts() {
+        let path = Path::new("test.dgt");
+
+        assert_eq!(
+            parse_transcripts("Siva: Hi\n", path),
+            Err(TranscriptError::NoEntry(Span::new(path.to_owned(), 1, 1)))
+        );
+
+        assert_eq!(
+            parse_transcripts("% Start\n\n> zero\n", path),
+            Err(TranscriptError::BadChoice(
+                Span::new(path.to_owned(), 3, 1),
+                "zero".to_owned()
+            ))
+        );
+    }
+}